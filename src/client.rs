@@ -1,4 +1,7 @@
+use std::collections::HashSet;
+
 use serenity::{
+    async_trait,
     framework::{
         standard::{
             macros::{group, hook},
@@ -6,7 +9,8 @@ use serenity::{
         },
         StandardFramework,
     },
-    model::prelude::*,
+    http::Http,
+    model::{application::interaction::Interaction, prelude::*},
     prelude::*,
 };
 use songbird::serenity::SerenityInit;
@@ -18,7 +22,17 @@ use crate::framework::{
     L0C0B0T_HANDLER,
 };
 
-use crate::commands::{music::MUSIC_GROUP, testing::TESTING_GROUP};
+use crate::commands::{
+    music::{
+        cache::AudioCache,
+        controls::handle_player_button,
+        playlists::Playlists,
+        sessions::{restore_sessions, Sessions},
+        slash::{handle_music_command, register_music_commands},
+        MUSIC_GROUP,
+    },
+    testing::TESTING_GROUP,
+};
 
 #[group]
 #[commands(setting)]
@@ -55,6 +69,32 @@ async fn normal_message(ctx: &Context, msg: &Message) {
     L0C0B0T_HANDLER.dispatch(ctx, msg).await;
 }
 
+struct Handler;
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, _ready: Ready) {
+        match register_music_commands(&ctx).await {
+            Ok(commands) => println!("Registered {} slash commands", commands.len()),
+            Err(why) => println!("Could not register slash commands: {why:?}"),
+        }
+
+        restore_sessions(&ctx).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::ApplicationCommand(command) => {
+                handle_music_command(&ctx, &command).await;
+            }
+            Interaction::MessageComponent(component) => {
+                handle_player_button(&ctx, &component).await;
+            }
+            _ => {}
+        }
+    }
+}
+
 pub struct L0C0B0TClient {
     client: Client,
 }
@@ -68,10 +108,21 @@ impl L0C0B0TClient {
 
         let handler_ref = HandlerRef::new(&L0C0B0T_HANDLER);
 
+        // The owners can use the commands marked as `owners_only`
+        let mut owners = HashSet::new();
+
+        match Http::new(token).get_current_application_info().await {
+            Ok(info) => {
+                owners.insert(info.team.map_or(info.owner.id, |team| team.owner_user_id));
+            }
+            Err(why) => println!("Could not get the owner of the bot: {why:?}"),
+        }
+
         let client = serenity::Client::builder(token, intents)
+            .event_handler(Handler)
             .framework(
                 StandardFramework::new()
-                    .configure(|c| c.prefix("!").case_insensitivity(true))
+                    .configure(|c| c.prefix("!").case_insensitivity(true).owners(owners))
                     .before(before)
                     .after(after)
                     .normal_message(normal_message)
@@ -85,7 +136,13 @@ impl L0C0B0TClient {
         {
             let mut data = client.data.write().await;
             data.insert::<Settings>(Settings::try_load().unwrap_or_else(Settings::new));
+            data.insert::<Sessions>(Sessions::try_load().unwrap_or_default());
+            data.insert::<Playlists>(Playlists::try_load().unwrap_or_default());
             data.insert::<HandlerRef>(handler_ref);
+
+            if let Some(cache) = AudioCache::try_load() {
+                data.insert::<AudioCache>(cache);
+            }
         }
 
         Ok(Self { client })
@@ -0,0 +1,565 @@
+// On-disk cache of the audio of the songs
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, prelude::GuildId},
+    prelude::{Context, Mutex, TypeMapKey},
+};
+use songbird::input::{
+    children_to_reader, error::Result as InputResult, restartable::Restart, Codec, Container,
+    Input, Metadata, Restartable,
+};
+use tokio::sync::Semaphore;
+
+use super::{
+    errors::MusicCommandError,
+    filters::{spawn_filtered_ffmpeg, YTDL_FORMAT_ARGS},
+    responses::cache_stats_response,
+    state::get_music_state,
+    utils::youtube_id,
+};
+
+lazy_static! {
+    /// The maximum size of the audio cache in megabytes, after which
+    /// the least recently played songs are deleted
+    static ref AUDIO_CACHE_MAX_MB: u64 = env::var("AUDIO_CACHE_MAX_MB")
+        .unwrap_or_else(|_| "1024".to_string())
+        .parse()
+        .unwrap();
+
+    /// Limits how many songs are downloaded to the cache at the same time
+    static ref DOWNLOAD_PERMITS: Semaphore = Semaphore::new(MAX_CONCURRENT_DOWNLOADS);
+}
+
+/// The maximum number of songs downloaded to the cache at the same time
+const MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
+/// The name of the file that lists the cached songs, inside the cache directory
+const CACHE_INDEX_FILE: &str = "index.ron";
+
+/// The maximum number of resolved searches remembered, after which the least
+/// recently used ones are forgotten
+const MAX_CACHED_SEARCHES: usize = 1000;
+
+/// A song saved in the audio cache, with the metadata needed to play it offline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedSong {
+    url: String,
+    title: Option<String>,
+    channel: Option<String>,
+    duration: Option<Duration>,
+    thumbnail: Option<String>,
+    /// The size of the audio file in bytes
+    size: u64,
+    /// The last time the song was played from the cache, in seconds since the epoch
+    last_used: u64,
+}
+
+impl CachedSong {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            title: self.title.clone(),
+            channel: self.channel.clone(),
+            duration: self.duration,
+            thumbnail: self.thumbnail.clone(),
+            source_url: Some(self.url.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+/// The video a yt-dlp search resolved to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedSearch {
+    id: String,
+    /// The last time the search was made, in seconds since the epoch
+    last_used: u64,
+}
+
+/// The contents of the index file of the cache
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    songs: HashMap<String, CachedSong>,
+    /// Missing in indexes saved before searches were stored
+    #[serde(default)]
+    searches: HashMap<String, CachedSearch>,
+}
+
+impl CacheIndex {
+    /// Read an index file. Indexes saved before searches were stored only
+    /// have the list of songs.
+    fn read(path: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+
+        ron::from_str(&contents).ok().or_else(|| {
+            let songs = ron::from_str(&contents).ok()?;
+            Some(Self {
+                songs,
+                searches: HashMap::new(),
+            })
+        })
+    }
+}
+
+/// The audio of the songs played before, stored by their video id so they
+/// don't have to be downloaded again
+#[derive(Debug)]
+pub struct AudioCache {
+    dir: PathBuf,
+    songs: HashMap<String, CachedSong>,
+    /// The songs being downloaded, so they are not downloaded twice
+    downloading: HashSet<String>,
+    /// The video id each yt-dlp search resolved to, so searched songs can
+    /// be found in the cache without searching again
+    searches: HashMap<String, CachedSearch>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Returns the current time in seconds since the epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+impl AudioCache {
+    /// Try to load the cache from the directory specified in the `AUDIO_CACHE_PATH`
+    /// environment variable, creating it if it doesn't exist.
+    ///
+    /// Returns `None` if the environment variable is not set or the directory could not be created.
+    pub fn try_load() -> Option<Self> {
+        let path = env::var("AUDIO_CACHE_PATH").ok()?;
+        let dir = PathBuf::from(&path);
+        fs::create_dir_all(&dir).ok()?;
+
+        let CacheIndex {
+            mut songs,
+            searches,
+        } = CacheIndex::read(&dir.join(CACHE_INDEX_FILE)).unwrap_or_default();
+
+        // Files deleted by hand are forgotten
+        songs.retain(|id, _| dir.join(format!("{id}.audio")).is_file());
+
+        println!("Loaded audio cache from {path} with {} songs", songs.len());
+
+        Some(Self {
+            dir,
+            songs,
+            downloading: HashSet::new(),
+            searches,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// Get the path of the audio of a song.
+    fn audio_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.audio"))
+    }
+
+    /// Get a cached song, marking it as recently used. The new time is
+    /// saved to disk with the next downloaded song.
+    ///
+    /// Counts a hit if the song is cached and a miss if it's not.
+    fn get(&mut self, id: &str) -> Option<(PathBuf, CachedSong)> {
+        let Some(song) = self.songs.get_mut(id) else {
+            self.misses += 1;
+            return None;
+        };
+
+        self.hits += 1;
+        song.last_used = now();
+        let song = song.clone();
+
+        Some((self.audio_path(id), song))
+    }
+
+    /// Get the video id a search resolved to, marking it as recently used.
+    ///
+    /// Counts a miss if the search was never resolved.
+    fn get_search(&mut self, query: &str) -> Option<String> {
+        let Some(search) = self.searches.get_mut(query) else {
+            self.misses += 1;
+            return None;
+        };
+
+        search.last_used = now();
+
+        Some(search.id.clone())
+    }
+
+    /// Remember the video id a search resolved to, forgetting the least
+    /// recently used searches while there are too many.
+    fn insert_search(&mut self, query: &str, id: &str) {
+        self.searches.insert(
+            query.to_string(),
+            CachedSearch {
+                id: id.to_string(),
+                last_used: now(),
+            },
+        );
+
+        while self.searches.len() > MAX_CACHED_SEARCHES {
+            let Some(oldest) = self
+                .searches
+                .iter()
+                .min_by_key(|(_, search)| search.last_used)
+                .map(|(query, _)| query.clone())
+            else {
+                break;
+            };
+
+            self.searches.remove(&oldest);
+        }
+    }
+
+    /// Mark a song as being downloaded.
+    ///
+    /// Returns `false` if the song is already cached or being downloaded.
+    fn start_download(&mut self, id: &str) -> bool {
+        !self.songs.contains_key(id) && self.downloading.insert(id.to_string())
+    }
+
+    /// Add a downloaded song, forgetting the least recently used ones until
+    /// the cache fits in its size limit. Pinned songs are never forgotten.
+    ///
+    /// Returns the audio files of the forgotten songs, to be deleted.
+    fn insert(&mut self, id: &str, song: CachedSong, pinned: &HashSet<String>) -> Vec<PathBuf> {
+        self.downloading.remove(id);
+        self.songs.insert(id.to_string(), song);
+
+        let max_size = *AUDIO_CACHE_MAX_MB * 1024 * 1024;
+        let mut evicted = vec![];
+
+        while self.size() > max_size {
+            let Some(oldest) = self
+                .songs
+                .iter()
+                .filter(|(id, _)| !pinned.contains(*id))
+                .min_by_key(|(_, song)| song.last_used)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+
+            evicted.push(self.audio_path(&oldest));
+            self.songs.remove(&oldest);
+        }
+
+        evicted
+    }
+
+    /// Get the total size of the cached audio in bytes.
+    fn size(&self) -> u64 {
+        self.songs.values().map(|song| song.size).sum()
+    }
+}
+
+impl TypeMapKey for AudioCache {
+    type Value = Self;
+}
+
+lazy_static! {
+    /// Held while the cache index is written, so an older list of songs
+    /// never overwrites a newer one
+    static ref SAVE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Save the list of cached songs and resolved searches to the cache directory.
+/// The file is written without holding the lock to the bot data.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+async fn save_cache(ctx: &Context) -> Result<(), MusicCommandError> {
+    let _guard = SAVE_LOCK.lock().await;
+
+    let (path, contents) = {
+        let data = ctx.data.read().await;
+        let Some(cache) = data.get::<AudioCache>() else {
+            return Ok(());
+        };
+
+        let index = CacheIndex {
+            songs: cache.songs.clone(),
+            searches: cache.searches.clone(),
+        };
+
+        let contents = ron::to_string(&index).map_err(|_| MusicCommandError::CacheSaveFailed)?;
+
+        (cache.dir.join(CACHE_INDEX_FILE), contents)
+    };
+
+    tokio::task::spawn_blocking(move || fs::write(path, contents))
+        .await
+        .map_err(|_| MusicCommandError::CacheSaveFailed)?
+        .map_err(|_| MusicCommandError::CacheSaveFailed)
+}
+
+/// Recreates a source from a cached audio file with the filters active
+/// in the guild each time it starts or seeks
+struct CachedRestarter {
+    ctx: Context,
+    guild_id: GuildId,
+    path: PathBuf,
+    song: CachedSong,
+}
+
+#[async_trait]
+impl Restart for CachedRestarter {
+    async fn call_restart(&mut self, time: Option<Duration>) -> InputResult<Input> {
+        let filters = get_music_state(&self.ctx, self.guild_id).await.filters;
+
+        let ffmpeg =
+            spawn_filtered_ffmpeg(&filters, time, &self.path.to_string_lossy(), Stdio::null())?;
+
+        Ok(Input::new(
+            true,
+            children_to_reader::<f32>(vec![ffmpeg]),
+            Codec::FloatPcm,
+            Container::Raw,
+            Some(self.song.metadata()),
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> InputResult<(Option<Metadata>, Codec, Container)> {
+        Ok((Some(self.song.metadata()), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+/// Creates a source that plays a song from the audio cache
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the source will play in
+/// * `url` - The link to the song, or a yt-dlp search that was resolved before
+///
+/// ## Returns
+///
+/// * `Some(Restartable)` - The song is cached
+/// * `None` - The song is not cached, or there is no cache
+pub(super) async fn cached_source(
+    ctx: &Context,
+    guild_id: GuildId,
+    url: &str,
+) -> Option<Restartable> {
+    let (path, song) = {
+        let mut data = ctx.data.write().await;
+        let cache = data.get_mut::<AudioCache>()?;
+
+        let id = match youtube_id(url) {
+            Some(id) => id.to_string(),
+            None => cache.get_search(url)?,
+        };
+
+        cache.get(&id)?
+    };
+
+    let restarter = CachedRestarter {
+        ctx: ctx.clone(),
+        guild_id,
+        path,
+        song,
+    };
+
+    Restartable::new(restarter, true).await.ok()
+}
+
+/// Returns the video ids of the songs in the queue of every guild
+async fn queued_ids(ctx: &Context) -> HashSet<String> {
+    let mut ids = HashSet::new();
+
+    let Some(manager) = songbird::get(ctx).await else {
+        return ids;
+    };
+
+    for guild_id in ctx.cache.guilds() {
+        let Some(handler_lock) = manager.get(guild_id) else {
+            continue;
+        };
+
+        let queue = handler_lock.lock().await.queue().current_queue();
+
+        ids.extend(queue.iter().filter_map(|track| {
+            let url = track.metadata().source_url.clone()?;
+            youtube_id(&url).map(str::to_string)
+        }));
+    }
+
+    ids
+}
+
+/// Remembers the video a yt-dlp search resolved to, so the next time it's
+/// played it can be found in the cache. The searches are saved to disk with
+/// the next downloaded song
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `query` - The search, e.g. `ytsearch1:query`
+/// * `url` - The link to the video it resolved to
+pub(super) async fn remember_search(ctx: &Context, query: &str, url: &str) {
+    if youtube_id(query).is_some() {
+        return;
+    }
+
+    let Some(id) = youtube_id(url) else {
+        return;
+    };
+
+    let mut data = ctx.data.write().await;
+
+    if let Some(cache) = data.get_mut::<AudioCache>() {
+        cache.insert_search(query, id);
+    }
+}
+
+/// Downloads the audio of a song to the cache in the background, unless it
+/// is already cached. Only a few songs are downloaded at the same time, the
+/// rest wait for their turn
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `metadata` - The metadata of the song, saved along its audio
+pub(super) async fn cache_song(ctx: &Context, metadata: &Metadata) {
+    let Some(url) = metadata.source_url.clone() else {
+        return;
+    };
+
+    let Some(id) = youtube_id(&url).map(str::to_string) else {
+        return;
+    };
+
+    let path = {
+        let mut data = ctx.data.write().await;
+
+        let Some(cache) = data.get_mut::<AudioCache>() else {
+            return;
+        };
+
+        if !cache.start_download(&id) {
+            return;
+        }
+
+        cache.audio_path(&id)
+    };
+
+    let ctx = ctx.clone();
+    let mut song = CachedSong {
+        url,
+        title: metadata.title.clone(),
+        channel: metadata.channel.clone(),
+        duration: metadata.duration,
+        thumbnail: metadata.thumbnail.clone(),
+        size: 0,
+        last_used: now(),
+    };
+
+    tokio::spawn(async move {
+        let _permit = DOWNLOAD_PERMITS.acquire().await;
+
+        // Downloaded under another name so a failed download is never played
+        let download_path = path.with_extension("download");
+
+        let downloaded = tokio::process::Command::new("yt-dlp")
+            .args(YTDL_FORMAT_ARGS)
+            .arg(&song.url)
+            .arg("-o")
+            .arg(&download_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success())
+            && fs::rename(&download_path, &path).is_ok();
+
+        let size = fs::metadata(&path)
+            .ok()
+            .filter(|_| downloaded)
+            .map(|file| file.len());
+
+        let Some(size) = size else {
+            println!("Could not cache {}", song.url);
+
+            fs::remove_file(&download_path).ok();
+
+            let mut data = ctx.data.write().await;
+            data.get_mut::<AudioCache>()
+                .unwrap()
+                .downloading
+                .remove(&id);
+
+            return;
+        };
+
+        song.size = size;
+
+        // The files of the queued songs may be playing, so they are kept
+        let pinned = queued_ids(&ctx).await;
+
+        let evicted = {
+            let mut data = ctx.data.write().await;
+            data.get_mut::<AudioCache>()
+                .unwrap()
+                .insert(&id, song, &pinned)
+        };
+
+        for path in evicted {
+            fs::remove_file(path).ok();
+        }
+
+        if let Err(why) = save_cache(&ctx).await {
+            println!("Could not save the audio cache: {why}");
+        }
+    });
+}
+
+/////////////////////////
+//      Commands       //
+/////////////////////////
+
+#[command]
+#[only_in(guilds)]
+#[owners_only]
+#[aliases("caché")]
+pub async fn cache(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mode = args.single::<String>().unwrap_or_default();
+
+    if mode != "stats" {
+        return Err("Modo inválido: `stats`".into());
+    }
+
+    let response = {
+        let data = ctx.data.read().await;
+        let cache = data
+            .get::<AudioCache>()
+            .ok_or(MusicCommandError::CacheDisabled)?;
+
+        cache_stats_response(
+            cache.hits,
+            cache.misses,
+            cache.songs.len(),
+            cache.size(),
+            *AUDIO_CACHE_MAX_MB * 1024 * 1024,
+        )
+    };
+
+    msg.channel_id.say(&ctx.http, response).await?;
+
+    Ok(())
+}
@@ -6,7 +6,12 @@ use serenity::{
     prelude::{Context, Mentionable},
 };
 
-use super::{errors::MusicCommandError, utils::get_handler_lock};
+use super::{
+    errors::MusicCommandError,
+    sessions::clear_saved_session,
+    settings::DJ_CHECK,
+    utils::{get_handler_lock, Invocation},
+};
 
 /////////////////////////
 //      Commands       //
@@ -15,15 +20,20 @@ use super::{errors::MusicCommandError, utils::get_handler_lock};
 #[command]
 #[only_in(guilds)]
 pub async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_join(ctx, &msg.into()).await
+}
+
+/// Joins the voice channel of the user
+pub(super) async fn run_join(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
     let handler = handler_lock.lock().await;
 
     let channel = handler.current_channel().unwrap();
     let channel_mention = ChannelId(channel.0).mention();
 
-    msg.channel_id
-        .say(&ctx.http, format!("**Conectando a {channel_mention}...**"))
+    invocation
+        .say(ctx, format!("**Conectando a {channel_mention}...**"))
         .await?;
 
     Ok(())
@@ -32,17 +42,28 @@ pub async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 #[aliases("dc", "disconnect", "disc")]
+#[checks(dj)]
 pub async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_leave(ctx, &msg.into()).await
+}
+
+/// Leaves the voice channel
+pub(super) async fn run_leave(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    {
+        let mut handler = handler_lock.lock().await;
 
-    let mut handler = handler_lock.lock().await;
+        handler.queue().stop();
+        handler
+            .leave()
+            .await
+            .map_err(|_| MusicCommandError::Generic)?;
+    }
 
-    handler
-        .leave()
-        .await
-        .map_err(|_| MusicCommandError::Generic)?;
+    clear_saved_session(ctx, invocation.guild_id()).await;
 
-    msg.channel_id.say(&ctx.http, "Chau 😔").await?;
+    invocation.say(ctx, "Chau 😔").await?;
 
     Ok(())
 }
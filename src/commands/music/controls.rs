@@ -0,0 +1,188 @@
+// Buttons to control the player from the music embeds
+
+use std::sync::Arc;
+
+use serenity::{
+    builder::CreateComponents,
+    model::{
+        application::{
+            component::ButtonStyle,
+            interaction::{
+                message_component::MessageComponentInteraction, InteractionResponseType,
+            },
+        },
+        prelude::{ChannelId, GuildId, UserId},
+    },
+    prelude::{Context, Mutex},
+};
+use songbird::{tracks::PlayMode, Call};
+
+use super::{
+    errors::MusicCommandError,
+    responses::{loop_mode_response, skip_vote_response, song_skipped_response},
+    sessions::clear_saved_session,
+    settings::{check_dj, is_music_channel},
+    utils::{
+        cycle_loop_mode, pause_song, resume_song, shuffle_queue, skip_or_vote, stop_player,
+        SkipOutcome,
+    },
+};
+
+/// The prefix of the custom ids of the player buttons, so other
+/// components are ignored by `handle_player_button`
+const PLAYER_BUTTON_PREFIX: &str = "player:";
+
+/// Adds a row with the player controls to a message
+pub(super) fn player_buttons(components: &mut CreateComponents) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        for (action, emoji) in [
+            ("pause", '⏯'),
+            ("skip", '⏭'),
+            ("stop", '⏹'),
+            ("loop", '🔁'),
+            ("shuffle", '🔀'),
+        ] {
+            row.create_button(|b| {
+                b.custom_id(format!("{PLAYER_BUTTON_PREFIX}{action}"))
+                    .emoji(emoji)
+                    .style(ButtonStyle::Secondary)
+            });
+        }
+
+        row
+    })
+}
+
+/// Handles a click on one of the player buttons, answering privately
+/// when it can't be done
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `interaction` - The click on the button
+pub async fn handle_player_button(ctx: &Context, interaction: &MessageComponentInteraction) {
+    let Some(action) = interaction
+        .data
+        .custom_id
+        .strip_prefix(PLAYER_BUTTON_PREFIX)
+    else {
+        return;
+    };
+
+    let content = match run_player_action(ctx, interaction, action).await {
+        Ok(content) => content,
+        Err(why) => format!("⚠️ **Error**: {why}"),
+    };
+
+    // Only the user that clicked sees the answer, so the buttons don't flood the channel
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| d.content(content).ephemeral(true))
+        })
+        .await
+        .ok();
+}
+
+/// Returns the call of a guild if the user is listening in it
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild of the call
+/// * `user_id` - The user that has to be in the call
+///
+/// ## Returns
+///
+/// * `Ok(Arc<Mutex<Call>>)` - The lock to the songbird handler
+/// * `Err(MusicCommandError)` - The bot or the user are not in the same voice channel
+async fn get_listener_handler_lock(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Arc<Mutex<Call>>, MusicCommandError> {
+    let user_channel = ctx
+        .cache
+        .guild(guild_id)
+        .and_then(|guild| guild.voice_states.get(&user_id)?.channel_id)
+        .ok_or(MusicCommandError::NoVoiceChannel)?;
+
+    let handler_lock = songbird::get(ctx)
+        .await
+        .and_then(|manager| manager.get(guild_id))
+        .ok_or(MusicCommandError::NotInBotVoiceChannel)?;
+
+    let bot_channel = handler_lock.lock().await.current_channel();
+
+    if bot_channel.map(|channel| ChannelId(channel.0)) != Some(user_channel) {
+        return Err(MusicCommandError::NotInBotVoiceChannel);
+    }
+
+    Ok(handler_lock)
+}
+
+/// Runs the action of a player button with the same checks as its command
+///
+/// ## Returns
+///
+/// * `Ok(String)` - The message to answer with
+/// * `Err(MusicCommandError)` - The action could not be done
+async fn run_player_action(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    action: &str,
+) -> Result<String, MusicCommandError> {
+    let guild_id = interaction.guild_id.ok_or(MusicCommandError::Generic)?;
+    let user_id = interaction.user.id;
+
+    if !is_music_channel(ctx, guild_id, interaction.channel_id).await {
+        return Err(MusicCommandError::NotInMusicChannel);
+    }
+
+    let handler_lock = get_listener_handler_lock(ctx, guild_id, user_id).await?;
+
+    let response = match action {
+        "pause" => {
+            let current = handler_lock.lock().await.queue().current();
+            let track = current.ok_or(MusicCommandError::NoSongPlaying)?;
+
+            let paused = track
+                .get_info()
+                .await
+                .is_ok_and(|info| info.playing == PlayMode::Pause);
+
+            if paused {
+                resume_song(handler_lock).await?;
+                "▶️ **Reanudando...**".to_string()
+            } else {
+                pause_song(handler_lock).await?;
+                "⏸️ **Pausando...**".to_string()
+            }
+        }
+        "skip" => match skip_or_vote(ctx, guild_id, user_id, handler_lock).await? {
+            SkipOutcome::Skipped(track) => song_skipped_response(&track),
+            SkipOutcome::Voted { votes, required } => skip_vote_response(votes, required),
+        },
+        "stop" => {
+            check_dj(ctx, guild_id, user_id).await?;
+            stop_player(handler_lock).await?;
+            clear_saved_session(ctx, guild_id).await;
+            "⏹️ **Parando...**".to_string()
+        }
+        "loop" => loop_mode_response(cycle_loop_mode(ctx, guild_id, handler_lock).await),
+        "shuffle" => {
+            let handler = handler_lock.lock().await;
+            let queue = handler.queue();
+
+            if queue.len() <= 2 {
+                return Err(MusicCommandError::EmptyQueue);
+            }
+
+            shuffle_queue(queue);
+            "🔀 **Mezclando la cola...**".to_string()
+        }
+        _ => return Err(MusicCommandError::Generic),
+    };
+
+    Ok(response)
+}
@@ -1,23 +1,87 @@
 use thiserror::Error;
 
+#[derive(Debug, Clone, Copy, Error)]
+pub enum DurationError {
+    #[error("no escribiste ningún tiempo")]
+    Empty,
+    #[error("`{0}` no es válido en un tiempo")]
+    InvalidCharacter(char),
+    #[error("`{0}` no es una unidad, usá `h`, `m` o `s`")]
+    UnknownUnit(char),
+    #[error("falta un número antes de `{0}`")]
+    MissingNumber(char),
+    #[error("los {0} tienen que ser menos de 60")]
+    OutOfRange(&'static str),
+    #[error("usá como mucho `hh:mm:ss`")]
+    TooManyParts,
+    #[error("es demasiado largo")]
+    Overflow,
+}
+
 #[derive(Debug, Clone, Copy, Error)]
 pub enum MusicCommandError {
     #[error("No estoy tocando nada")]
     NoSongPlaying,
-    #[error("Tiempo inválido")]
-    InvalidTime,
+    #[error("Tiempo inválido: {0}")]
+    InvalidTime(#[from] DurationError),
     #[error("Índice inválido")]
     InvalidQueueIndex,
     #[error("No encontré la canción")]
     FailedVideoSearch,
+    #[error("No puedo reproducir links de ese sitio")]
+    UnsupportedHost,
+    #[error("No pude leer la playlist")]
+    FailedPlaylistSearch,
+    #[error("No existe esa playlist")]
+    PlaylistNotFound,
+    #[error("Nombre de playlist inválido")]
+    InvalidPlaylistName,
+    #[error("No pude guardar la playlist")]
+    PlaylistSaveFailed,
+    #[error("La canción dura más del máximo de {0} minutos")]
+    TrackTooLong(u64),
+    #[error("No sé cuánto dura la canción y el máximo es de {0} minutos")]
+    UnknownDuration(u64),
+    #[error("No podés tener más de {0} canciones en la cola")]
+    UserQueueLimit(usize),
+    #[error("La cola ya tiene el máximo de {0} canciones")]
+    QueueFull(usize),
+    #[error("No hay canciones anteriores")]
+    EmptyHistory,
+    #[error("No hay canciones salteadas para restaurar")]
+    NothingToUnskip,
+    #[error("Adjuntá un archivo con la cola")]
+    NoAttachment,
+    #[error("El archivo no es una cola válida")]
+    InvalidQueueFile,
+    #[error("No hay canciones para eliminar")]
+    NoSongsToRemove,
     #[error("La cola está vacía")]
     EmptyQueue,
     #[error("No estás en un canal de voz")]
     NoVoiceChannel,
+    #[error("Tenés que estar en mi canal de voz")]
+    NotInBotVoiceChannel,
+    #[error("Los controles solo funcionan en el canal de música")]
+    NotInMusicChannel,
+    #[error("Necesitás el rol de DJ para hacer eso")]
+    NotDj,
     #[error("No me pude unir al canal")]
     FailedToJoinChannel,
+    #[error("El volumen tiene que estar entre 0 y 200")]
+    InvalidVolume,
+    #[error("Filtro inválido: `bassboost`, `nightcore`, `vaporwave`, `speed [0.5-2]`, `normalize` o `clear`")]
+    InvalidFilter,
     #[error("Este formato no se puede seekear")]
     SeekFailed,
+    #[error("No hay ninguna sesión guardada")]
+    NoSavedSession,
+    #[error("No pude guardar la sesión")]
+    SessionSaveFailed,
+    #[error("No pude guardar la caché de audio")]
+    CacheSaveFailed,
+    #[error("La caché de audio está desactivada")]
+    CacheDisabled,
     #[error("Error")]
     Generic,
 }
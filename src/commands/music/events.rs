@@ -0,0 +1,187 @@
+// Songbird event handlers for the player
+
+use std::time::Duration;
+
+use serenity::{async_trait, model::prelude::GuildId, prelude::Context};
+use songbird::{
+    tracks::{PlayMode, TrackHandle},
+    Event, EventContext, EventHandler,
+};
+
+use super::{
+    cache::cache_song,
+    controls::player_buttons,
+    filters::output_time,
+    responses::now_playing_embed,
+    settings::{get_announce_tracks, get_autoplay},
+    state::{get_music_state, update_music_state, LoopMode},
+    utils::{autoplay_related, requeue_track, TrackChannel, TrackReplaced},
+};
+
+/// Posts the now playing embed of a track in the channel it was requested in,
+/// deleting the previous announcement
+async fn announce_track(ctx: &Context, guild_id: GuildId, track: &TrackHandle) -> Option<()> {
+    let channel_id = *track.typemap().read().await.get::<TrackChannel>()?;
+
+    let next = {
+        let handler_lock = songbird::get(ctx).await?.get(guild_id)?;
+        let handler = handler_lock.lock().await;
+        handler.queue().current_queue().get(1).cloned()
+    };
+
+    let state = get_music_state(ctx, guild_id).await;
+    let embed = now_playing_embed(ctx, track, next.as_ref(), &state).await;
+
+    let message = channel_id
+        .send_message(&ctx.http, |m| m.set_embed(embed).components(player_buttons))
+        .await
+        .ok()?;
+
+    let previous = update_music_state(ctx, guild_id, |state| {
+        state.announcement.replace((channel_id, message.id))
+    })
+    .await;
+
+    if let Some((channel_id, message_id)) = previous {
+        channel_id.delete_message(&ctx.http, message_id).await.ok();
+    }
+
+    Some(())
+}
+
+/// Fires once when a track starts playing for the first time
+pub(super) struct TrackStartHandler {
+    pub(super) ctx: Context,
+    pub(super) guild_id: GuildId,
+    /// The time to jump to when the track starts
+    pub(super) start: Option<Duration>,
+}
+
+#[async_trait]
+impl EventHandler for TrackStartHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track([(_, track)]) = ctx else {
+            return None;
+        };
+
+        let state = get_music_state(&self.ctx, self.guild_id).await;
+
+        if let Some(start) = self.start {
+            track.seek_time(output_time(&state.filters, start)).ok();
+        }
+
+        if state.loop_mode == LoopMode::Track {
+            track.enable_loop().ok();
+        }
+
+        if get_announce_tracks(&self.ctx, self.guild_id).await {
+            // Posting the announcement would hold up the other events of the driver
+            let ctx = self.ctx.clone();
+            let guild_id = self.guild_id;
+            let track = (*track).clone();
+
+            tokio::spawn(async move { announce_track(&ctx, guild_id, &track).await });
+        }
+
+        None
+    }
+}
+
+/// Fires when any track of the guild ends
+pub(super) struct TrackEndHandler {
+    pub(super) ctx: Context,
+    pub(super) guild_id: GuildId,
+}
+
+#[async_trait]
+impl EventHandler for TrackEndHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(track_list) = ctx else {
+            return None;
+        };
+
+        // Replaced tracks keep playing as their copy
+        let mut ended = Vec::with_capacity(track_list.len());
+
+        for &(track_state, track) in track_list.iter() {
+            if !track.typemap().read().await.contains_key::<TrackReplaced>() {
+                ended.push((track_state, track));
+            }
+        }
+
+        let track_list = ended;
+
+        // Only the songs that were heard go to the history: the ones that finished
+        // and the one that was playing when it was skipped or stopped. Queued songs
+        // that are stopped or removed never started playing.
+        update_music_state(&self.ctx, self.guild_id, |state| {
+            for (track_state, track) in track_list.iter() {
+                if track_state.playing == PlayMode::End || track_state.play_time > Duration::ZERO {
+                    state.push_history((*track).clone());
+                }
+            }
+        })
+        .await;
+
+        // Songs are only cached once they were played to the end
+        for (track_state, track) in track_list.iter() {
+            if track_state.playing == PlayMode::End {
+                cache_song(&self.ctx, track.metadata()).await;
+            }
+        }
+
+        let state = get_music_state(&self.ctx, self.guild_id).await;
+
+        let queue_empty = {
+            let manager = songbird::get(&self.ctx).await?;
+            let handler_lock = manager.get(self.guild_id)?;
+            let handler = handler_lock.lock().await;
+            handler.queue().is_empty()
+        };
+
+        if state.loop_mode == LoopMode::Queue {
+            for (track_state, track) in track_list.iter() {
+                // Stopping the player drains the queue before stopping the tracks,
+                // so stopped tracks are only sent back when there is still a queue
+                // and they were playing (i.e. they were skipped)
+                let skipped = !queue_empty && track_state.play_time > Duration::ZERO;
+
+                if track_state.playing == PlayMode::End || skipped {
+                    let ctx = self.ctx.clone();
+                    let guild_id = self.guild_id;
+                    let track = (*track).clone();
+
+                    tokio::spawn(async move {
+                        let handler_lock = songbird::get(&ctx).await?.get(guild_id)?;
+
+                        requeue_track(&ctx, guild_id, handler_lock, &track)
+                            .await
+                            .ok()
+                    });
+                }
+            }
+        } else if queue_empty && get_autoplay(&self.ctx, self.guild_id).await {
+            // Only keep playing when the last song finished, not when the player was stopped
+            let finished = track_list
+                .iter()
+                .rev()
+                .find(|(track_state, _)| track_state.playing == PlayMode::End);
+
+            if let Some((_, track)) = finished {
+                let ctx = self.ctx.clone();
+                let guild_id = self.guild_id;
+                let track = (*track).clone();
+
+                tokio::spawn(async move {
+                    let handler_lock = songbird::get(&ctx).await?.get(guild_id)?;
+
+                    autoplay_related(&ctx, guild_id, handler_lock, &track)
+                        .await
+                        .ok()
+                });
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,430 @@
+// Audio filters applied through ffmpeg
+
+use std::{
+    fmt::Display,
+    mem::discriminant,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use serenity::{
+    async_trait,
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, prelude::GuildId},
+    prelude::Context,
+};
+use songbird::input::{
+    children_to_reader,
+    error::{Error as InputError, Result as InputResult},
+    restartable::Restart,
+    Codec, Container, Input, Metadata, Restartable,
+};
+
+use super::{
+    cache::{cached_source, remember_search},
+    errors::MusicCommandError,
+    responses::filters_response,
+    state::{get_music_state, update_music_state},
+    utils::{get_handler_lock, restart_current_track, Invocation},
+};
+
+/// The format arguments passed to yt-dlp, the same ones songbird uses
+pub(super) const YTDL_FORMAT_ARGS: [&str; 7] = [
+    "-f",
+    "webm[abr>0]/bestaudio/best",
+    "-R",
+    "infinite",
+    "--no-playlist",
+    "--ignore-config",
+    "--no-warnings",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum AudioFilter {
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    /// Changes the tempo without changing the pitch
+    Speed(f32),
+    /// EBU R128 loudness normalization
+    Normalize,
+}
+
+impl AudioFilter {
+    /// Parses a filter from its name and an optional value
+    ///
+    /// ## Arguments
+    ///
+    /// * `name` - The name of the filter
+    /// * `value` - The value of the filter, only used by `speed`
+    ///
+    /// ## Returns
+    ///
+    /// * `Ok(AudioFilter)` - The parsed filter
+    /// * `Err(MusicCommandError)` - The filter or its value is not valid
+    fn parse(name: &str, value: Option<&str>) -> Result<Self, MusicCommandError> {
+        match name {
+            "bassboost" | "bass" => Ok(Self::BassBoost),
+            "nightcore" => Ok(Self::Nightcore),
+            "vaporwave" => Ok(Self::Vaporwave),
+            "normalize" | "loudnorm" => Ok(Self::Normalize),
+            "speed" => {
+                let speed = value
+                    .and_then(|value| value.trim_end_matches('x').parse::<f32>().ok())
+                    .filter(|speed| (0.5..=2.0).contains(speed))
+                    .ok_or(MusicCommandError::InvalidFilter)?;
+
+                Ok(Self::Speed(speed))
+            }
+            _ => Err(MusicCommandError::InvalidFilter),
+        }
+    }
+
+    /// Returns the ffmpeg audio filter that applies this effect
+    fn ffmpeg_filter(&self) -> String {
+        match self {
+            Self::BassBoost => "bass=g=10".to_string(),
+            Self::Nightcore => "asetrate=48000*1.25,aresample=48000".to_string(),
+            Self::Vaporwave => "asetrate=48000*0.8,aresample=48000".to_string(),
+            Self::Speed(speed) => format!("atempo={speed}"),
+            Self::Normalize => "loudnorm=I=-16:TP=-1.5:LRA=11".to_string(),
+        }
+    }
+
+    /// Returns how many times faster than the original the audio plays with this filter
+    fn speed_factor(&self) -> f64 {
+        match self {
+            Self::Nightcore => 1.25,
+            Self::Vaporwave => 0.8,
+            Self::Speed(speed) => f64::from(*speed),
+            Self::BassBoost | Self::Normalize => 1.0,
+        }
+    }
+
+    /// Returns whether two filters are the same effect, regardless of their values
+    fn same_kind(&self, other: &Self) -> bool {
+        discriminant(self) == discriminant(other)
+    }
+}
+
+impl Display for AudioFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BassBoost => write!(f, "bassboost"),
+            Self::Nightcore => write!(f, "nightcore"),
+            Self::Vaporwave => write!(f, "vaporwave"),
+            Self::Speed(speed) => write!(f, "speed {speed}x"),
+            Self::Normalize => write!(f, "normalize"),
+        }
+    }
+}
+
+/// Converts the position of a track, which songbird counts in played audio,
+/// to the time in the song it corresponds to with some filters active
+///
+/// ## Arguments
+///
+/// * `filters` - The active filters
+/// * `position` - The position of the track
+///
+/// ## Returns
+///
+/// * `Duration` - The time in the song
+pub(super) fn source_time(filters: &[AudioFilter], position: Duration) -> Duration {
+    position.mul_f64(speed_factor(filters))
+}
+
+/// Converts a time in a song to the position of the track with some filters
+/// active. The inverse of `source_time`
+///
+/// ## Arguments
+///
+/// * `filters` - The active filters
+/// * `time` - The time in the song
+///
+/// ## Returns
+///
+/// * `Duration` - The position of the track, to seek to
+pub(super) fn output_time(filters: &[AudioFilter], time: Duration) -> Duration {
+    time.div_f64(speed_factor(filters))
+}
+
+/// Returns how many times faster than the original the audio plays with some filters
+fn speed_factor(filters: &[AudioFilter]) -> f64 {
+    filters.iter().map(AudioFilter::speed_factor).product()
+}
+
+/// Starts an ffmpeg process that decodes an audio to raw PCM with some filters
+///
+/// ## Arguments
+///
+/// * `filters` - The filters to apply
+/// * `time` - The position of the track to start decoding at
+/// * `input` - The file to decode, or `-` to read it from `stdin`
+/// * `stdin` - The input of the process
+///
+/// ## Returns
+///
+/// * `Ok(Child)` - The ffmpeg process, writing to its `stdout`
+/// * `Err(std::io::Error)` - ffmpeg could not be started
+pub(super) fn spawn_filtered_ffmpeg(
+    filters: &[AudioFilter],
+    time: Option<Duration>,
+    input: &str,
+    stdin: Stdio,
+) -> std::io::Result<Child> {
+    let mut ffmpeg = Command::new("ffmpeg");
+
+    if let Some(time) = time {
+        let time = source_time(filters, time);
+        ffmpeg.args(["-ss", &format!("{:.3}", time.as_secs_f64())]);
+    }
+
+    ffmpeg.args(["-i", input]);
+
+    if !filters.is_empty() {
+        let filter_graph = filters
+            .iter()
+            .map(AudioFilter::ffmpeg_filter)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        ffmpeg.args(["-af", &filter_graph]);
+    }
+
+    ffmpeg
+        .args(["-f", "s16le", "-ac", "2", "-ar", "48000"])
+        .args(["-acodec", "pcm_f32le", "-"])
+        .stdin(stdin)
+        .stderr(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+}
+
+/// Recreates a yt-dlp source with the filters active in the guild
+/// each time it starts or seeks
+struct FilteredRestarter {
+    ctx: Context,
+    guild_id: GuildId,
+    uri: String,
+    /// The metadata of the song if it's already known, so yt-dlp doesn't
+    /// have to load it before the song starts
+    metadata: Option<Metadata>,
+}
+
+#[async_trait]
+impl Restart for FilteredRestarter {
+    async fn call_restart(&mut self, time: Option<Duration>) -> InputResult<Input> {
+        let filters = get_music_state(&self.ctx, self.guild_id).await.filters;
+
+        let mut youtube_dl = Command::new("yt-dlp")
+            .args(YTDL_FORMAT_ARGS)
+            .arg(&self.uri)
+            .args(["-o", "-"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let taken_stdout = youtube_dl.stdout.take().ok_or(InputError::Stdout)?;
+
+        let ffmpeg = spawn_filtered_ffmpeg(&filters, time, "-", taken_stdout.into())?;
+
+        Ok(Input::new(
+            true,
+            children_to_reader::<f32>(vec![youtube_dl, ffmpeg]),
+            Codec::FloatPcm,
+            Container::Raw,
+            None,
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> InputResult<(Option<Metadata>, Codec, Container)> {
+        if let Some(metadata) = self.metadata.take() {
+            return Ok((Some(metadata), Codec::FloatPcm, Container::Raw));
+        }
+
+        let output = tokio::process::Command::new("yt-dlp")
+            .arg("-j")
+            .args(YTDL_FORMAT_ARGS)
+            .arg(&self.uri)
+            .stdin(Stdio::null())
+            .output()
+            .await?;
+
+        let line = output.stdout.split(|&b| b == b'\n').next().unwrap_or(&[]);
+
+        let value = serde_json::from_slice(line).map_err(|error| InputError::Json {
+            error,
+            parsed_text: String::from_utf8_lossy(line).to_string(),
+        })?;
+
+        let metadata = Metadata::from_ytdl_output(value);
+
+        // Searches are resolved once, so restarting the song plays the same video
+        if let Some(url) = &metadata.source_url {
+            remember_search(&self.ctx, &self.uri, url).await;
+            self.uri = url.clone();
+        }
+
+        Ok((Some(metadata), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+/// Creates a lazy source for a link or a yt-dlp search that plays
+/// with the filters active in the guild. Cached songs are played from
+/// the audio cache, without yt-dlp
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the source will play in
+/// * `uri` - The link or search (e.g. `ytsearch1:query`) to play
+///
+/// ## Returns
+///
+/// * `Ok(Restartable)` - The source, with its metadata loaded
+/// * `Err(MusicCommandError)` - yt-dlp could not load the song
+pub(super) async fn filtered_source(
+    ctx: &Context,
+    guild_id: GuildId,
+    uri: String,
+) -> Result<Restartable, MusicCommandError> {
+    if let Some(source) = cached_source(ctx, guild_id, &uri).await {
+        return Ok(source);
+    }
+
+    let restarter = FilteredRestarter {
+        ctx: ctx.clone(),
+        guild_id,
+        uri,
+        metadata: None,
+    };
+
+    Restartable::new(restarter, true)
+        .await
+        .map_err(|_| MusicCommandError::FailedVideoSearch)
+}
+
+/// Creates a lazy source for a song whose metadata is already known, like the
+/// entries of a playlist. yt-dlp is not run until the song starts playing
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the source will play in
+/// * `url` - The link to the song
+/// * `metadata` - The metadata the song is queued with
+///
+/// ## Returns
+///
+/// * `Ok(Restartable)` - The source, with the given metadata
+/// * `Err(MusicCommandError)` - The source could not be created
+pub(super) async fn lazy_source(
+    ctx: &Context,
+    guild_id: GuildId,
+    url: String,
+    metadata: Metadata,
+) -> Result<Restartable, MusicCommandError> {
+    if let Some(source) = cached_source(ctx, guild_id, &url).await {
+        return Ok(source);
+    }
+
+    let restarter = FilteredRestarter {
+        ctx: ctx.clone(),
+        guild_id,
+        uri: url,
+        metadata: Some(metadata),
+    };
+
+    Restartable::new(restarter, true)
+        .await
+        .map_err(|_| MusicCommandError::FailedVideoSearch)
+}
+
+/////////////////////////
+//      Commands       //
+/////////////////////////
+
+#[command]
+#[only_in(guilds)]
+#[aliases("filtro", "fx")]
+pub async fn filter(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = args.single::<String>().ok();
+
+    run_filter(ctx, &msg.into(), name.as_deref(), args.current()).await
+}
+
+/// Toggles a filter, clears them all, or shows the active ones when no
+/// filter is given
+pub(super) async fn run_filter(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    name: Option<&str>,
+    value: Option<&str>,
+) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    // Without arguments, just show the active filters
+    let Some(name) = name else {
+        let state = get_music_state(ctx, guild_id).await;
+
+        invocation
+            .say(ctx, filters_response(&state.filters))
+            .await?;
+
+        return Ok(());
+    };
+
+    let name = name.to_lowercase();
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let old_filters = get_music_state(ctx, guild_id).await.filters;
+
+    let filters = if name == "clear" || name == "off" {
+        update_music_state(ctx, guild_id, |state| {
+            state.filters.clear();
+            state.filters.clone()
+        })
+        .await
+    } else {
+        let filter = AudioFilter::parse(&name, value)?;
+
+        update_music_state(ctx, guild_id, |state| {
+            let existing = state.filters.iter().position(|f| f.same_kind(&filter));
+
+            match existing {
+                // Changing the speed replaces the old one, anything else is toggled
+                Some(index) if state.filters[index] != filter => state.filters[index] = filter,
+                Some(index) => {
+                    state.filters.remove(index);
+                }
+                None => state.filters.push(filter),
+            }
+
+            state.filters.clone()
+        })
+        .await
+    };
+
+    // Restarting the current song builds its source again with the new filters
+    let current = handler_lock.lock().await.queue().current();
+
+    if let Some(track) = current {
+        if let Ok(info) = track.get_info().await {
+            // The same time of the song is at a different position at another speed.
+            // Going back a bit keeps it a cheap seek back when the speed doesn't change
+            let time =
+                source_time(&old_filters, info.position).saturating_sub(Duration::from_millis(20));
+
+            restart_current_track(ctx, guild_id, handler_lock, &track, &filters, time)
+                .await
+                .ok();
+        }
+    }
+
+    invocation.say(ctx, filters_response(&filters)).await?;
+
+    Ok(())
+}
@@ -1,17 +1,32 @@
 use serenity::framework::standard::macros::group;
 
+pub mod cache;
 mod errors;
+pub mod sessions;
 pub mod settings;
 mod utils;
 
 mod channels;
+pub mod controls;
+mod events;
+mod filters;
 mod play;
+pub mod playlists;
 mod queue;
+mod queue_file;
 mod responses;
+mod search;
+pub mod slash;
+mod state;
 
+use cache::*;
 use channels::*;
+use filters::*;
 use play::*;
+use playlists::*;
 use queue::*;
+use search::*;
+use sessions::*;
 
 use settings::IN_MUSIC_CHANNEL_CHECK;
 
@@ -19,19 +34,36 @@ use settings::IN_MUSIC_CHANNEL_CHECK;
 #[commands(
     play,
     play_top,
+    search,
     skip,
+    skipto,
+    unskip,
+    previous,
     pause,
     stop,
     seek,
+    forward,
+    rewind,
+    loop_,
+    volume,
+    filter,
     queue,
+    history,
     now_playing,
     insert,
     move_,
     remove,
+    dedupe,
     replace,
     clear,
+    shuffle,
+    fair,
+    autoplay,
+    playlist,
     join,
-    leave
+    leave,
+    resume_session,
+    cache
 )]
 #[checks(in_music_channel)]
 struct Music;
@@ -1,19 +1,31 @@
 // Playback control
 
+use std::time::Duration;
+
 use serenity::{
     framework::standard::{macros::command, Args, CommandResult},
     model::channel::Message,
     prelude::Context,
 };
 
+use crate::utils::Volume;
+
 use super::{
+    controls::player_buttons,
     errors::MusicCommandError,
     responses::{
-        searching_response, song_added_embed, song_seeked_response, song_skipped_response,
+        loop_mode_response, playlist_added_embed, searching_response, skip_vote_response,
+        skipped_to_response, song_added_embed, song_previous_response, song_seeked_response,
+        song_skipped_response, volume_response,
     },
+    sessions::clear_saved_session,
+    settings::DJ_CHECK,
+    state::{get_music_state, update_music_state},
     utils::{
-        get_handler_lock, insert_song, parse_duration, pause_song, resume_song, search_song,
-        stop_player, QueuePosition,
+        cycle_loop_mode, get_handler_lock, get_volume, insert_playlist, insert_song,
+        is_playlist_url, parse_duration, parse_seek_target, pause_song, play_next_now,
+        reinsert_track, resume_song, search_song, seek_track, skip_or_vote, skip_to, stop_player,
+        Invocation, QueuePosition, Requester, SeekTarget, SkipOutcome,
     },
 };
 
@@ -21,35 +33,63 @@ use super::{
 #[only_in(guilds)]
 #[aliases("p")]
 pub async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_play(ctx, &msg.into(), args.rest()).await
+}
+
+/// Adds a song or a playlist to the end of the queue, or resumes the player
+/// when there is no query
+pub(super) async fn run_play(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    query: &str,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    if is_playlist_url(query) {
+        invocation.reply(ctx, searching_response(query)).await?;
+
+        let playlist = insert_playlist(
+            ctx,
+            invocation.guild_id(),
+            Requester::from(invocation),
+            handler_lock,
+            query,
+            QueuePosition::Last,
+        )
+        .await?;
 
-    let query = args.rest();
+        invocation
+            .send_embed(
+                ctx,
+                playlist_added_embed(invocation.author(), &playlist),
+                |c| c,
+            )
+            .await?;
 
-    if !query.is_empty() {
+        Ok(())
+    } else if !query.is_empty() {
         // If there is a query, search for a video and play it
-        msg.reply(ctx, searching_response(query)).await?;
-        let source = search_song(query).await?;
+        invocation.reply(ctx, searching_response(query)).await?;
+        let (source, start) = search_song(ctx, invocation.guild_id(), query).await?;
 
         let position = insert_song(
-            msg.author.id,
+            ctx,
+            invocation.guild_id(),
+            Requester::from(invocation),
             handler_lock.clone(),
             source.into(),
+            start,
             QueuePosition::Last,
         )
         .await?;
 
         println!("Inserted song at position {position}");
 
-        let embed = {
-            let handler = handler_lock.lock().await;
-            let queue = handler.queue().current_queue();
-
-            song_added_embed(ctx, &queue, position).await
-        };
+        let queue = handler_lock.lock().await.queue().current_queue();
+        let filters = get_music_state(ctx, invocation.guild_id()).await.filters;
+        let embed = song_added_embed(ctx, &queue, position, &filters).await;
 
-        msg.channel_id
-            .send_message(&ctx.http, |m| m.set_embed(embed))
-            .await?;
+        invocation.send_embed(ctx, embed, player_buttons).await?;
 
         Ok(())
     } else {
@@ -61,12 +101,18 @@ pub async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 #[only_in(guilds)]
 #[aliases("pete", "pt")]
 pub async fn play_top(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_play_top(ctx, &msg.into(), args.rest()).await
+}
 
-    let query = args.rest();
+/// Adds a song or a playlist right after the current song
+pub(super) async fn run_play_top(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    query: &str,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
-    msg.reply(ctx, searching_response(query)).await?;
-    let source = search_song(query).await?;
+    invocation.reply(ctx, searching_response(query)).await?;
 
     let song_playing = {
         let handler = handler_lock.lock().await;
@@ -81,19 +127,46 @@ pub async fn play_top(ctx: &Context, msg: &Message, args: Args) -> CommandResult
         QueuePosition::Last
     };
 
-    let position =
-        insert_song(msg.author.id, handler_lock.clone(), source.into(), position).await?;
+    if is_playlist_url(query) {
+        let playlist = insert_playlist(
+            ctx,
+            invocation.guild_id(),
+            Requester::from(invocation),
+            handler_lock,
+            query,
+            position,
+        )
+        .await?;
 
-    let embed = {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue().current_queue();
+        invocation
+            .send_embed(
+                ctx,
+                playlist_added_embed(invocation.author(), &playlist),
+                |c| c,
+            )
+            .await?;
 
-        song_added_embed(ctx, &queue, position).await
-    };
+        return Ok(());
+    }
 
-    msg.channel_id
-        .send_message(&ctx.http, |m| m.set_embed(embed))
-        .await?;
+    let (source, start) = search_song(ctx, invocation.guild_id(), query).await?;
+
+    let position = insert_song(
+        ctx,
+        invocation.guild_id(),
+        Requester::from(invocation),
+        handler_lock.clone(),
+        source.into(),
+        start,
+        position,
+    )
+    .await?;
+
+    let queue = handler_lock.lock().await.queue().current_queue();
+    let filters = get_music_state(ctx, invocation.guild_id()).await.filters;
+    let embed = song_added_embed(ctx, &queue, position, &filters).await;
+
+    invocation.send_embed(ctx, embed, player_buttons).await?;
 
     Ok(())
 }
@@ -102,30 +175,204 @@ pub async fn play_top(ctx: &Context, msg: &Message, args: Args) -> CommandResult
 #[only_in(guilds)]
 #[aliases("s", "fs")]
 pub async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
-    let handler = handler_lock.lock().await;
+    run_skip(ctx, &msg.into()).await
+}
+
+/// Skips the current song, or votes to skip it
+pub(super) async fn run_skip(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let outcome = skip_or_vote(
+        ctx,
+        invocation.guild_id(),
+        invocation.author().id,
+        handler_lock,
+    )
+    .await?;
+
+    let response = match outcome {
+        SkipOutcome::Skipped(track) => song_skipped_response(&track),
+        SkipOutcome::Voted { votes, required } => skip_vote_response(votes, required),
+    };
+
+    invocation.say(ctx, response).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("st", "saltar")]
+#[checks(dj)]
+pub async fn skipto(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let index = args
+        .single::<usize>()
+        .map_err(|_| MusicCommandError::InvalidQueueIndex)?;
+
+    run_skipto(ctx, &msg.into(), index).await
+}
+
+/// Drops the songs before a position of the queue and plays the one at it
+pub(super) async fn run_skipto(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    index: usize,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let (target, skipped) = skip_to(handler_lock, index).await?;
+
+    let count = skipped.len();
+
+    update_music_state(ctx, invocation.guild_id(), |state| state.skipped = skipped).await;
+
+    invocation
+        .say(ctx, skipped_to_response(&target, count))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("undo")]
+#[checks(dj)]
+pub async fn unskip(ctx: &Context, msg: &Message) -> CommandResult {
+    run_unskip(ctx, &msg.into()).await
+}
+
+/// Undoes the last `skipto`: the song that was playing plays again, followed
+/// by the songs it skipped, and then the current song
+pub(super) async fn run_unskip(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let skipped =
+        update_music_state(ctx, guild_id, |state| std::mem::take(&mut state.skipped)).await;
+
+    // The first skipped song is the one that was playing
+    let Some((previous, dropped)) = skipped.split_first() else {
+        return Err(MusicCommandError::NothingToUnskip.into());
+    };
+
+    let count = dropped.len();
+    invocation
+        .reply(ctx, format!("♻️ **Restaurando {count} canciones...**"))
+        .await?;
+
+    let song_playing = {
+        let handler = handler_lock.lock().await;
+        handler.queue().current().is_some()
+    };
 
-    let queue = handler.queue();
+    let mut inserted = 0;
+    let mut previous_restored = false;
 
-    let track = queue.current().ok_or(MusicCommandError::NoSongPlaying)?;
+    for (i, track) in skipped.iter().enumerate() {
+        // Keep the songs in the order they had, right after the current one
+        let position = if song_playing {
+            QueuePosition::Index(inserted + 1)
+        } else {
+            QueuePosition::Last
+        };
+
+        if reinsert_track(ctx, guild_id, handler_lock.clone(), track, position)
+            .await
+            .is_ok()
+        {
+            inserted += 1;
+            previous_restored |= i == 0;
+        }
+    }
+
+    if song_playing && inserted > 0 {
+        // Play the first restored song now and leave the current one after the rest
+        play_next_now(handler_lock.clone()).await?;
+
+        let handler = handler_lock.lock().await;
+        handler.queue().modify_queue(|q| {
+            if let Some(current) = q.remove(1) {
+                q.insert(inserted, current);
+            }
+        });
+    }
+
+    let restored = inserted - usize::from(previous_restored);
 
-    queue.skip().unwrap();
+    if previous_restored {
+        invocation
+            .say(ctx, song_previous_response(previous))
+            .await?;
+    }
 
-    msg.channel_id
-        .say(&ctx.http, song_skipped_response(&track))
+    invocation
+        .say(
+            ctx,
+            format!("✅ **{restored} de {count} canciones restauradas**"),
+        )
         .await?;
 
     Ok(())
 }
 
+#[command]
+#[only_in(guilds)]
+#[aliases("back", "prev")]
+pub async fn previous(ctx: &Context, msg: &Message) -> CommandResult {
+    run_previous(ctx, &msg.into()).await
+}
+
+/// Plays the last finished song again
+pub(super) async fn run_previous(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let track = update_music_state(ctx, guild_id, |state| state.history.pop_back())
+        .await
+        .ok_or(MusicCommandError::EmptyHistory)?;
+
+    let song_playing = {
+        let handler = handler_lock.lock().await;
+        handler.queue().current().is_some()
+    };
+
+    let position = if song_playing {
+        // Put the previous song right after the current one, then swap them
+        QueuePosition::Index(1)
+    } else {
+        QueuePosition::Last
+    };
+
+    if let Err(why) = reinsert_track(ctx, guild_id, handler_lock.clone(), &track, position).await {
+        // Keep the song in the history so it can be retried
+        update_music_state(ctx, guild_id, |state| state.history.push_back(track)).await;
+        return Err(why.into());
+    }
+
+    if song_playing {
+        play_next_now(handler_lock).await?;
+    }
+
+    invocation.say(ctx, song_previous_response(&track)).await?;
+
+    Ok(())
+}
+
 #[command]
 #[only_in(guilds)]
 pub async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_pause(ctx, &msg.into()).await
+}
+
+/// Pauses the current song
+pub(super) async fn run_pause(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
     pause_song(handler_lock).await?;
 
-    msg.channel_id.say(&ctx.http, "⏸️ **Pausando...**").await?;
+    invocation.say(ctx, "⏸️ **Pausando...**").await?;
 
     Ok(())
 }
@@ -133,7 +380,7 @@ pub async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 pub async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    let handler_lock = get_handler_lock(ctx, &msg.into()).await?;
 
     resume_song(handler_lock).await?;
 
@@ -144,40 +391,179 @@ pub async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
 
 #[command]
 #[only_in(guilds)]
+#[checks(dj)]
 pub async fn stop(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_stop(ctx, &msg.into()).await
+}
+
+/// Stops the player and clears the queue
+pub(super) async fn run_stop(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
     stop_player(handler_lock).await?;
 
+    clear_saved_session(ctx, invocation.guild_id()).await;
+
     Ok(())
 }
 
 #[command]
 #[only_in(guilds)]
-pub async fn seek(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+#[aliases("loop", "repeat")]
+pub async fn loop_(ctx: &Context, msg: &Message) -> CommandResult {
+    run_loop(ctx, &msg.into()).await
+}
+
+/// Switches to the next loop mode
+pub(super) async fn run_loop(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let loop_mode = cycle_loop_mode(ctx, invocation.guild_id(), handler_lock).await;
+
+    invocation.say(ctx, loop_mode_response(loop_mode)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("vol", "v")]
+pub async fn volume(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let volume = if args.is_empty() {
+        None
+    } else {
+        let volume = args
+            .parse::<Volume>()
+            .map_err(|_| MusicCommandError::InvalidVolume)?;
+
+        Some(volume)
+    };
+
+    run_volume(ctx, &msg.into(), volume).await
+}
+
+/// Sets the volume of the player, or shows it when no volume is given
+pub(super) async fn run_volume(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    volume: Option<Volume>,
+) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    // Without arguments, just show the current volume
+    let Some(Volume(volume)) = volume else {
+        let volume = get_volume(ctx, guild_id).await;
+
+        invocation.say(ctx, volume_response(volume)).await?;
+
+        return Ok(());
+    };
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    update_music_state(ctx, guild_id, |state| state.volume = Some(volume)).await;
+
+    // Apply the new volume to the songs that are already in the queue
+    {
+        let handler = handler_lock.lock().await;
+
+        for track in handler.queue().current_queue() {
+            track.set_volume(volume as f32 / 100.0).ok();
+        }
+    }
+
+    invocation.say(ctx, volume_response(volume)).await?;
+
+    Ok(())
+}
+
+/// The time skipped by `forward` and `rewind` when no time is given
+const SEEK_STEP: Duration = Duration::from_secs(10);
+
+/// Seeks the current song and replies with the old and new positions
+async fn seek_current(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    target: SeekTarget,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+    let filters = get_music_state(ctx, invocation.guild_id()).await.filters;
     let handler = handler_lock.lock().await;
 
     let track = handler
         .queue()
         .current()
         .ok_or(MusicCommandError::NoSongPlaying)?;
-    let duration = track.metadata().duration.unwrap();
 
-    let arg = args.rest();
-    let position = parse_duration(arg).ok_or(MusicCommandError::InvalidTime)?;
+    let (old_position, new_position) = seek_track(&track, target, &filters).await?;
+
+    invocation
+        .say(ctx, song_seeked_response(old_position, new_position))
+        .await?;
 
-    if position > duration {
-        return Err(MusicCommandError::InvalidTime.into());
+    Ok(())
+}
+
+/// Parses the time skipped by `forward` and `rewind`, which is `SEEK_STEP` if empty
+fn parse_seek_step(time: &str) -> Result<Duration, MusicCommandError> {
+    match time.trim() {
+        "" => Ok(SEEK_STEP),
+        time => Ok(parse_duration(time)?),
     }
+}
 
-    track
-        .seek_time(position)
-        .map_err(|_| MusicCommandError::SeekFailed)?;
+#[command]
+#[only_in(guilds)]
+#[checks(dj)]
+pub async fn seek(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    run_seek(ctx, &msg.into(), args.rest()).await
+}
 
-    msg.channel_id
-        .say(&ctx.http, song_seeked_response(position))
-        .await?;
+/// Seeks the current song to an absolute or relative time
+pub(super) async fn run_seek(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    time: &str,
+) -> CommandResult {
+    let target = parse_seek_target(time).map_err(MusicCommandError::from)?;
 
-    Ok(())
+    seek_current(ctx, invocation, target).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("ff", "adelantar")]
+#[checks(dj)]
+pub async fn forward(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    run_forward(ctx, &msg.into(), args.rest()).await
+}
+
+/// Skips ahead in the current song
+pub(super) async fn run_forward(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    time: &str,
+) -> CommandResult {
+    let offset = parse_seek_step(time)?;
+
+    seek_current(ctx, invocation, SeekTarget::Forward(offset)).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("rw", "retroceder")]
+#[checks(dj)]
+pub async fn rewind(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    run_rewind(ctx, &msg.into(), args.rest()).await
+}
+
+/// Goes back in the current song
+pub(super) async fn run_rewind(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    time: &str,
+) -> CommandResult {
+    let offset = parse_seek_step(time)?;
+
+    seek_current(ctx, invocation, SeekTarget::Backward(offset)).await
 }
@@ -0,0 +1,288 @@
+// Saved playlists
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fs::{self, File},
+    io::BufReader,
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, prelude::GuildId},
+    prelude::{Context, Mutex, TypeMapKey},
+};
+
+use super::{
+    errors::MusicCommandError,
+    responses::{playlist_added_embed, playlist_list_embed, playlist_show_embed},
+    utils::{get_handler_lock, insert_entries, Invocation, QueueEntry, QueuePosition, Requester},
+};
+
+/// A song saved in a playlist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSong {
+    pub url: String,
+    pub title: String,
+    /// The duration in seconds, missing in playlists saved before it was stored
+    #[serde(default)]
+    pub duration: Option<u64>,
+}
+
+/// The collection of saved playlists of each guild.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Playlists {
+    playlists: HashMap<u64, BTreeMap<String, Vec<SavedSong>>>,
+}
+
+impl Playlists {
+    /// Get a playlist of a guild.
+    pub fn get(&self, id: &GuildId, name: &str) -> Option<&Vec<SavedSong>> {
+        self.playlists.get(&id.0).and_then(|map| map.get(name))
+    }
+
+    /// Save a playlist for a guild, replacing the one with the same name.
+    pub fn set(&mut self, id: &GuildId, name: &str, songs: Vec<SavedSong>) {
+        let map = self.playlists.entry(id.0).or_default();
+        map.insert(name.to_string(), songs);
+    }
+
+    /// Delete a playlist of a guild.
+    ///
+    /// Returns `true` if the playlist existed.
+    pub fn remove(&mut self, id: &GuildId, name: &str) -> bool {
+        self.playlists
+            .get_mut(&id.0)
+            .and_then(|map| map.remove(name))
+            .is_some()
+    }
+
+    /// List the name and length of the playlists of a guild.
+    pub fn list(&self, id: &GuildId) -> Vec<(String, usize)> {
+        self.playlists
+            .get(&id.0)
+            .map(|map| {
+                map.iter()
+                    .map(|(name, songs)| (name.clone(), songs.len()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Try to load playlists from the file specified in the `PLAYLISTS_PATH` environment variable.
+    ///
+    /// Returns `None` if the environment variable is not set or the file could not be opened.
+    pub fn try_load() -> Option<Self> {
+        let path = env::var("PLAYLISTS_PATH").ok()?;
+        let file = File::open(&path).ok()?;
+        let reader = BufReader::new(file);
+        let playlists = ron::de::from_reader(reader).ok()?;
+        println!("Loaded playlists from {path}");
+        Some(playlists)
+    }
+}
+
+impl TypeMapKey for Playlists {
+    type Value = Self;
+}
+
+lazy_static! {
+    /// Held while the playlists file is written, so an older copy never
+    /// overwrites a newer one
+    static ref SAVE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Save the playlists to the file specified in the `PLAYLISTS_PATH` environment variable.
+/// The file is written without holding the lock to the bot data.
+///
+/// Does nothing if the environment variable is not set.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+async fn save_playlists(ctx: &Context) -> Result<(), MusicCommandError> {
+    let Ok(path) = env::var("PLAYLISTS_PATH") else {
+        return Ok(());
+    };
+
+    let _guard = SAVE_LOCK.lock().await;
+
+    let contents = {
+        let data = ctx.data.read().await;
+        let Some(playlists) = data.get::<Playlists>() else {
+            return Ok(());
+        };
+
+        ron::ser::to_string_pretty(playlists, ron::ser::PrettyConfig::default())
+            .map_err(|_| MusicCommandError::PlaylistSaveFailed)?
+    };
+
+    tokio::task::spawn_blocking(move || fs::write(path, contents))
+        .await
+        .map_err(|_| MusicCommandError::PlaylistSaveFailed)?
+        .map_err(|_| MusicCommandError::PlaylistSaveFailed)
+}
+
+/////////////////////////
+//      Commands       //
+/////////////////////////
+
+#[command]
+#[only_in(guilds)]
+#[aliases("pl")]
+pub async fn playlist(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mode: String = args.single()?;
+
+    run_playlist(ctx, &msg.into(), &mode, args.rest()).await
+}
+
+/// Saves, loads, lists, shows or deletes the playlists of the guild
+pub(super) async fn run_playlist(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    mode: &str,
+    name: &str,
+) -> CommandResult {
+    let name = name.trim().to_lowercase();
+
+    let guild_id = invocation.guild_id();
+
+    if mode != "list" && name.is_empty() {
+        return Err(MusicCommandError::InvalidPlaylistName.into());
+    }
+
+    match mode {
+        "save" => {
+            let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+            let songs: Vec<SavedSong> = {
+                let handler = handler_lock.lock().await;
+
+                handler
+                    .queue()
+                    .current_queue()
+                    .iter()
+                    .filter_map(|track| {
+                        let metadata = track.metadata();
+
+                        Some(SavedSong {
+                            url: metadata.source_url.clone()?,
+                            title: metadata.title.clone().unwrap_or_default(),
+                            duration: metadata.duration.map(|duration| duration.as_secs()),
+                        })
+                    })
+                    .collect()
+            };
+
+            if songs.is_empty() {
+                return Err(MusicCommandError::EmptyQueue.into());
+            }
+
+            let count = songs.len();
+
+            {
+                let mut data = ctx.data.write().await;
+                let playlists = data.get_mut::<Playlists>().unwrap();
+
+                playlists.set(&guild_id, &name, songs);
+            }
+
+            save_playlists(ctx).await?;
+
+            invocation
+                .reply(
+                    ctx,
+                    format!("💾 Playlist `{name}` guardada con {count} canciones"),
+                )
+                .await?;
+        }
+        "load" => {
+            let songs = {
+                let data = ctx.data.read().await;
+                data.get::<Playlists>()
+                    .unwrap()
+                    .get(&guild_id, &name)
+                    .cloned()
+                    .ok_or(MusicCommandError::PlaylistNotFound)?
+            };
+
+            let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+            invocation
+                .reply(ctx, format!("📂 **Cargando `{name}`...**"))
+                .await?;
+
+            let entries = songs.into_iter().map(|song| QueueEntry {
+                title: song.title,
+                url: song.url,
+                duration: song.duration.map(Duration::from_secs),
+            });
+
+            let playlist = insert_entries(
+                ctx,
+                guild_id,
+                Requester::from(invocation),
+                handler_lock,
+                entries,
+                QueuePosition::Last,
+            )
+            .await;
+
+            invocation
+                .send_embed(
+                    ctx,
+                    playlist_added_embed(invocation.author(), &playlist),
+                    |c| c,
+                )
+                .await?;
+        }
+        "list" => {
+            let playlists = {
+                let data = ctx.data.read().await;
+                data.get::<Playlists>().unwrap().list(&guild_id)
+            };
+
+            invocation
+                .send_embed(ctx, playlist_list_embed(&playlists), |c| c)
+                .await?;
+        }
+        "show" => {
+            let songs = {
+                let data = ctx.data.read().await;
+                data.get::<Playlists>()
+                    .unwrap()
+                    .get(&guild_id, &name)
+                    .cloned()
+                    .ok_or(MusicCommandError::PlaylistNotFound)?
+            };
+
+            invocation
+                .send_embed(ctx, playlist_show_embed(&name, &songs), |c| c)
+                .await?;
+        }
+        "delete" => {
+            {
+                let mut data = ctx.data.write().await;
+                let playlists = data.get_mut::<Playlists>().unwrap();
+
+                if !playlists.remove(&guild_id, &name) {
+                    return Err(MusicCommandError::PlaylistNotFound.into());
+                }
+            }
+
+            save_playlists(ctx).await?;
+
+            invocation
+                .reply(ctx, format!("🗑️ Playlist `{name}` eliminada"))
+                .await?;
+        }
+        _ => {
+            return Err("Modo inválido: `save`, `load`, `list`, `show` o `delete`".into());
+        }
+    }
+
+    Ok(())
+}
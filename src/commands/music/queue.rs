@@ -1,40 +1,183 @@
 // Queue functionality
 
+use std::time::Duration;
+
 use serenity::{
     framework::standard::{macros::command, Args, CommandResult},
-    model::channel::Message,
+    model::{
+        application::interaction::InteractionResponseType,
+        channel::Message,
+        prelude::{ChannelId, GuildId, MessageId},
+    },
     prelude::Context,
 };
+use songbird::tracks::TrackHandle;
 
 use super::{
+    controls::player_buttons,
     errors::MusicCommandError,
-    responses::{now_playing_embed, queue_embed, searching_response, song_added_embed},
-    utils::{get_handler_lock, insert_song, remove_song, search_song, QueuePosition},
+    queue_file::{run_queue_export, run_queue_import, QueueFileFormat},
+    responses::{
+        autoplay_response, fair_queue_response, history_embed, now_playing_embed,
+        playlist_added_embed, queue_embed, queue_page_buttons, queue_page_count,
+        searching_response, song_added_embed, songs_removed_response,
+    },
+    settings::{
+        get_autoplay, get_fair_queue, set_autoplay, set_fair_queue, DJ_CHECK, DJ_OR_REQUESTER_CHECK,
+    },
+    state::{get_music_state, update_music_state},
+    utils::{
+        autoplay_related, fair_reorder, get_handler_lock, insert_playlist, insert_song,
+        is_playlist_url, parse_removal_target, remove_duplicates, remove_song, remove_songs,
+        search_song, shuffle_queue, Invocation, QueuePosition, RemovalTarget, Requester,
+    },
 };
 
 /////////////////////////
 //      Commands       //
 /////////////////////////
 
+/// The time the buttons of the queue keep working after the last click
+const QUEUE_PAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[command]
 #[only_in(guilds)]
 #[aliases("q")]
-pub async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
-    let handler = handler_lock.lock().await;
+pub async fn queue(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    match args.current() {
+        Some("export") => {
+            args.advance();
+            let format = args.rest().parse::<QueueFileFormat>()?;
+
+            run_queue_export(ctx, &msg.into(), format).await
+        }
+        Some("import") => run_queue_import(ctx, &msg.into(), msg.attachments.first()).await,
+        _ => {
+            let page = args.single::<usize>().unwrap_or(1);
+
+            run_queue(ctx, &msg.into(), page).await
+        }
+    }
+}
 
-    let queue = handler.queue();
+/// Shows a page of the queue, with buttons to move between pages
+pub(super) async fn run_queue(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    page: usize,
+) -> CommandResult {
+    let guild_id = invocation.guild_id();
+    let mut page = page.max(1);
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let queue = {
+        let handler = handler_lock.lock().await;
+        handler.queue().current_queue()
+    };
 
-    if queue.current().is_none() {
+    if queue.is_empty() {
         return Err(MusicCommandError::NoSongPlaying.into());
     }
 
-    let embed = queue_embed(ctx, &queue.current_queue()).await;
+    let mut pages = queue_page_count(queue.len());
+    page = page.min(pages);
 
-    msg.channel_id
-        .send_message(&ctx, |m| m.set_embed(embed))
+    let state = get_music_state(ctx, guild_id).await;
+    let embed = queue_embed(ctx, &queue, &state, page).await;
+
+    let mut message = invocation
+        .send_embed(ctx, embed, |c| queue_page_buttons(c, page, pages))
         .await?;
 
+    while let Some(interaction) = message
+        .await_component_interaction(ctx)
+        .timeout(QUEUE_PAGE_TIMEOUT)
+        .await
+    {
+        // Only the caller can change the page
+        if interaction.user.id != invocation.author().id {
+            interaction
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content("Solo quien pidió la cola puede cambiar de página")
+                                .ephemeral(true)
+                        })
+                })
+                .await?;
+
+            continue;
+        }
+
+        // The queue may have changed since the last page
+        let queue = {
+            let handler = handler_lock.lock().await;
+            handler.queue().current_queue()
+        };
+
+        // The click still has to be answered before the buttons are removed
+        if queue.is_empty() {
+            interaction
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::DeferredUpdateMessage)
+                })
+                .await?;
+
+            break;
+        }
+
+        pages = queue_page_count(queue.len());
+        page = match interaction.data.custom_id.as_str() {
+            "prev" => page.saturating_sub(1),
+            _ => page + 1,
+        }
+        .clamp(1, pages);
+
+        let state = get_music_state(ctx, guild_id).await;
+        let embed = queue_embed(ctx, &queue, &state, page).await;
+
+        interaction
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.set_embed(embed)
+                            .components(|c| queue_page_buttons(c, page, pages))
+                    })
+            })
+            .await?;
+    }
+
+    message.edit(ctx, |m| m.components(|c| c)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("historial", "hist")]
+pub async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let page = args.single::<usize>().unwrap_or(1);
+
+    run_history(ctx, &msg.into(), page).await
+}
+
+/// Shows a page of the finished songs
+pub(super) async fn run_history(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    page: usize,
+) -> CommandResult {
+    let state = get_music_state(ctx, invocation.guild_id()).await;
+
+    if state.history.is_empty() {
+        return Err(MusicCommandError::EmptyHistory.into());
+    }
+
+    let embed = history_embed(ctx, &state.history, page).await;
+
+    invocation.send_embed(ctx, embed, |c| c).await?;
+
     Ok(())
 }
 
@@ -42,34 +185,129 @@ pub async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
 #[only_in(guilds)]
 #[aliases("nepe", "np")]
 pub async fn now_playing(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
-    let handler = handler_lock.lock().await;
+    run_now_playing(ctx, &msg.into()).await
+}
 
-    let track = handler
-        .queue()
-        .current()
-        .ok_or(MusicCommandError::NoSongPlaying)?;
+/// Shows the current song in a message that is kept up to date
+pub(super) async fn run_now_playing(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let guild_id = invocation.guild_id();
 
-    let embed = now_playing_embed(ctx, &track).await;
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
-    msg.channel_id
-        .send_message(&ctx.http, |m| m.set_embed(embed))
-        .await?;
+    let (track, next) = {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue().current_queue();
+
+        let track = queue
+            .first()
+            .cloned()
+            .ok_or(MusicCommandError::NoSongPlaying)?;
+
+        (track, queue.get(1).cloned())
+    };
+
+    let state = get_music_state(ctx, guild_id).await;
+    let embed = now_playing_embed(ctx, &track, next.as_ref(), &state).await;
+
+    let message = invocation.send_embed(ctx, embed, player_buttons).await?;
+
+    // Only the newest message of each guild is kept up to date
+    let live_message = (message.channel_id, message.id);
+    update_music_state(ctx, guild_id, |state| {
+        state.live_message = Some(live_message)
+    })
+    .await;
+
+    tokio::spawn(update_live_message(
+        ctx.clone(),
+        guild_id,
+        track,
+        live_message,
+    ));
 
     Ok(())
 }
 
+/// The time between each edit of a live `now_playing` message
+const LIVE_UPDATE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Edits a `now_playing` message periodically until its track ends, the bot
+/// leaves, or a newer `now_playing` message replaces it
+async fn update_live_message(
+    ctx: Context,
+    guild_id: GuildId,
+    track: TrackHandle,
+    live_message: (ChannelId, MessageId),
+) {
+    let (channel_id, message_id) = live_message;
+
+    loop {
+        tokio::time::sleep(LIVE_UPDATE_PERIOD).await;
+
+        let state = get_music_state(&ctx, guild_id).await;
+
+        if state.live_message != Some(live_message) {
+            return;
+        }
+
+        let Some(queue) = live_queue(&ctx, guild_id).await else {
+            break;
+        };
+
+        // Stop once the track is over
+        if queue.first().map(|current| current.uuid()) != Some(track.uuid()) {
+            break;
+        }
+
+        let embed = now_playing_embed(&ctx, &track, queue.get(1), &state).await;
+
+        let edited = channel_id
+            .edit_message(&ctx.http, message_id, |m| m.set_embed(embed))
+            .await;
+
+        // The message was deleted
+        if edited.is_err() {
+            break;
+        }
+    }
+
+    update_music_state(&ctx, guild_id, |state| {
+        if state.live_message == Some(live_message) {
+            state.live_message = None;
+        }
+    })
+    .await;
+}
+
+/// Returns the queue of a guild, or `None` if the bot is not in a voice channel
+async fn live_queue(ctx: &Context, guild_id: GuildId) -> Option<Vec<TrackHandle>> {
+    let handler_lock = songbird::get(ctx).await?.get(guild_id)?;
+    let handler = handler_lock.lock().await;
+
+    handler.current_channel()?;
+
+    Some(handler.queue().current_queue())
+}
+
 #[command]
 #[only_in(guilds)]
 #[aliases("i")]
 pub async fn insert(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
-
     let index = args.single::<usize>()?;
-    let query = args.rest();
 
-    msg.reply(ctx, searching_response(query)).await?;
-    let source = search_song(query).await?;
+    run_insert(ctx, &msg.into(), index, args.rest()).await
+}
+
+/// Adds a song or a playlist at a position of the queue
+pub(super) async fn run_insert(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    index: usize,
+    query: &str,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    invocation.reply(ctx, searching_response(query)).await?;
 
     let queue_length = {
         let handler = handler_lock.lock().await;
@@ -80,24 +318,46 @@ pub async fn insert(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
         return Err(MusicCommandError::InvalidQueueIndex.into());
     }
 
+    if is_playlist_url(query) {
+        let playlist = insert_playlist(
+            ctx,
+            invocation.guild_id(),
+            Requester::from(invocation),
+            handler_lock,
+            query,
+            QueuePosition::Index(index),
+        )
+        .await?;
+
+        invocation
+            .send_embed(
+                ctx,
+                playlist_added_embed(invocation.author(), &playlist),
+                |c| c,
+            )
+            .await?;
+
+        return Ok(());
+    }
+
+    let (source, start) = search_song(ctx, invocation.guild_id(), query).await?;
+
     let position = insert_song(
-        msg.author.id,
+        ctx,
+        invocation.guild_id(),
+        Requester::from(invocation),
         handler_lock.clone(),
         source.into(),
+        start,
         QueuePosition::Index(index),
     )
     .await?;
 
-    let embed = {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue().current_queue();
+    let queue = handler_lock.lock().await.queue().current_queue();
+    let filters = get_music_state(ctx, invocation.guild_id()).await.filters;
+    let embed = song_added_embed(ctx, &queue, position, &filters).await;
 
-        song_added_embed(ctx, &queue, position).await
-    };
-
-    msg.channel_id
-        .send_message(&ctx.http, |m| m.set_embed(embed))
-        .await?;
+    invocation.send_embed(ctx, embed, player_buttons).await?;
 
     Ok(())
 }
@@ -105,33 +365,78 @@ pub async fn insert(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
 #[command]
 #[only_in(guilds)]
 #[aliases("rm")]
+#[checks(dj_or_requester)]
 pub async fn remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let index = args.parse::<usize>().map_err(|_| "Índice inválido")?;
+    let target = parse_removal_target(args.rest())?;
+
+    run_remove(ctx, &msg.into(), target).await
+}
 
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+/// Removes songs from the queue by position, span of positions, or requester
+pub(super) async fn run_remove(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    target: RemovalTarget,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
-    let removed_title = remove_song(handler_lock, index).await?;
+    let removed = remove_songs(handler_lock, &target).await?;
 
-    msg.channel_id
-        .say(
-            &ctx.http,
-            format!("🗑️ **{removed_title}** fue eliminada de la cola"),
-        )
+    invocation
+        .say(ctx, songs_removed_response(&removed))
         .await?;
 
     Ok(())
 }
 
+#[command]
+#[only_in(guilds)]
+#[aliases("dedup", "repetidas")]
+#[checks(dj)]
+pub async fn dedupe(ctx: &Context, msg: &Message) -> CommandResult {
+    run_dedupe(ctx, &msg.into()).await
+}
+
+/// Removes the songs that are already in the queue
+pub(super) async fn run_dedupe(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let removed = {
+        let handler = handler_lock.lock().await;
+        remove_duplicates(handler.queue())
+    };
+
+    if removed.is_empty() {
+        invocation
+            .say(ctx, "✨ **No hay canciones repetidas en la cola**")
+            .await?;
+    } else {
+        invocation
+            .say(ctx, songs_removed_response(&removed))
+            .await?;
+    }
+
+    Ok(())
+}
+
 #[command]
 #[only_in(guilds)]
 #[aliases("re")]
+#[checks(dj)]
 pub async fn replace(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_replace(ctx, &msg.into(), args.rest()).await
+}
 
-    let query = args.rest();
+/// Replaces the last song of the queue
+pub(super) async fn run_replace(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    query: &str,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
 
-    msg.reply(ctx, searching_response(query)).await?;
-    let source = search_song(query).await?;
+    invocation.reply(ctx, searching_response(query)).await?;
+    let (source, start) = search_song(ctx, invocation.guild_id(), query).await?;
 
     let queue_length = {
         let handler = handler_lock.lock().await;
@@ -145,23 +450,21 @@ pub async fn replace(ctx: &Context, msg: &Message, args: Args) -> CommandResult
     remove_song(handler_lock.clone(), queue_length - 1).await?;
 
     let position = insert_song(
-        msg.author.id,
+        ctx,
+        invocation.guild_id(),
+        Requester::from(invocation),
         handler_lock.clone(),
         source.into(),
+        start,
         QueuePosition::Last,
     )
     .await?;
 
-    let embed = {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue().current_queue();
-
-        song_added_embed(ctx, &queue, position).await
-    };
+    let queue = handler_lock.lock().await.queue().current_queue();
+    let filters = get_music_state(ctx, invocation.guild_id()).await.filters;
+    let embed = song_added_embed(ctx, &queue, position, &filters).await;
 
-    msg.channel_id
-        .send_message(&ctx.http, |m| m.set_embed(embed))
-        .await?;
+    invocation.send_embed(ctx, embed, player_buttons).await?;
 
     Ok(())
 }
@@ -169,6 +472,7 @@ pub async fn replace(ctx: &Context, msg: &Message, args: Args) -> CommandResult
 #[command]
 #[only_in(guilds)]
 #[aliases("move", "mv")]
+#[checks(dj)]
 pub async fn move_(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let from = args
         .single::<usize>()
@@ -177,7 +481,17 @@ pub async fn move_(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
         .single::<usize>()
         .map_err(|_| MusicCommandError::InvalidQueueIndex)?;
 
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_move(ctx, &msg.into(), from, to).await
+}
+
+/// Moves a song to another position of the queue
+pub(super) async fn run_move(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    from: usize,
+    to: usize,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
     let handler = handler_lock.lock().await;
 
     let queue = handler.queue();
@@ -199,9 +513,9 @@ pub async fn move_(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
         q.insert(to, track);
     });
 
-    msg.channel_id
+    invocation
         .say(
-            &ctx.http,
+            ctx,
             format!("🚚 **{moved_title}** fue movida a la posición {to}"),
         )
         .await?;
@@ -211,8 +525,14 @@ pub async fn move_(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
 
 #[command]
 #[only_in(guilds)]
+#[checks(dj)]
 pub async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
-    let handler_lock = get_handler_lock(ctx, msg).await?;
+    run_clear(ctx, &msg.into()).await
+}
+
+/// Removes every song from the queue except the current one
+pub(super) async fn run_clear(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
     let handler = handler_lock.lock().await;
 
     let queue = handler.queue();
@@ -220,14 +540,104 @@ pub async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
     if queue.len() <= 1 {
         Err(MusicCommandError::EmptyQueue.into())
     } else {
-        handler.queue().modify_queue(|q| {
-            q.drain(1..);
+        let removed = handler.queue().modify_queue(|q| {
+            q.drain(1..)
+                .map(|queued| queued.handle())
+                .collect::<Vec<_>>()
         });
 
-        msg.channel_id
-            .say(&ctx.http, "💥 **Limpiando la cola...**")
-            .await?;
+        for track in removed {
+            track.stop().ok();
+        }
+
+        invocation.say(ctx, "💥 **Limpiando la cola...**").await?;
 
         Ok(())
     }
 }
+
+#[command]
+#[only_in(guilds)]
+#[aliases("mezclar")]
+pub async fn shuffle(ctx: &Context, msg: &Message) -> CommandResult {
+    run_shuffle(ctx, &msg.into()).await
+}
+
+/// Shuffles the songs after the current one
+pub(super) async fn run_shuffle(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+    let handler = handler_lock.lock().await;
+
+    let queue = handler.queue();
+
+    if queue.len() <= 2 {
+        return Err(MusicCommandError::EmptyQueue.into());
+    }
+
+    shuffle_queue(queue);
+
+    invocation.say(ctx, "🔀 **Mezclando la cola...**").await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("justo")]
+pub async fn fair(ctx: &Context, msg: &Message) -> CommandResult {
+    run_fair(ctx, &msg.into()).await
+}
+
+/// Toggles the fair queue, which makes the requesters take turns
+pub(super) async fn run_fair(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let enabled = !get_fair_queue(ctx, guild_id).await;
+    set_fair_queue(ctx, guild_id, enabled).await?;
+
+    if enabled {
+        let handler = handler_lock.lock().await;
+        fair_reorder(handler.queue()).await;
+    }
+
+    invocation.say(ctx, fair_queue_response(enabled)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("radio")]
+pub async fn autoplay(ctx: &Context, msg: &Message) -> CommandResult {
+    run_autoplay(ctx, &msg.into()).await
+}
+
+/// Toggles autoplay, starting it right away if the queue is empty
+pub(super) async fn run_autoplay(ctx: &Context, invocation: &Invocation<'_>) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let enabled = !get_autoplay(ctx, guild_id).await;
+    set_autoplay(ctx, guild_id, enabled).await?;
+
+    invocation.say(ctx, autoplay_response(enabled)).await?;
+
+    // Start right away if the queue already ran dry
+    if enabled {
+        let queue_empty = {
+            let handler = handler_lock.lock().await;
+            handler.queue().is_empty()
+        };
+
+        let last_track = get_music_state(ctx, guild_id).await.history.back().cloned();
+
+        if let (true, Some(track)) = (queue_empty, last_track) {
+            autoplay_related(ctx, guild_id, handler_lock, &track).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,403 @@
+// Queue export and import as files
+
+use std::{fmt::Write, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serenity::{framework::standard::CommandResult, model::channel::Attachment, prelude::Context};
+
+use super::{
+    errors::MusicCommandError,
+    responses::{invalid_lines_response, playlist_added_embed},
+    utils::{
+        get_handler_lock, get_requester, insert_entries, is_supported_url, Invocation,
+        PlaylistInsertion, QueueEntry, QueuePosition, Requester,
+    },
+};
+
+/// The largest file that can be imported, in bytes
+const MAX_QUEUE_FILE_SIZE: u64 = 512 * 1024;
+
+/// The number of a line of a queue file and why it could not be read
+type InvalidLine = (usize, &'static str);
+
+/// The formats a queue can be exported to
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) enum QueueFileFormat {
+    /// One link per line
+    #[default]
+    Urls,
+    /// An M3U playlist, with the title and duration of each song
+    M3u,
+    /// One `ExportedSong` per line, written in RON
+    Ron,
+}
+
+impl QueueFileFormat {
+    /// Returns the extension of the files in this format
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Urls => "txt",
+            Self::M3u => "m3u",
+            Self::Ron => "ron",
+        }
+    }
+}
+
+impl FromStr for QueueFileFormat {
+    type Err = MusicCommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "txt" | "urls" => Ok(Self::Urls),
+            "m3u" | "m3u8" => Ok(Self::M3u),
+            "ron" => Ok(Self::Ron),
+            _ => Err(MusicCommandError::InvalidQueueFile),
+        }
+    }
+}
+
+/// A song of an exported queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExportedSong {
+    url: String,
+    title: String,
+    /// The duration in seconds
+    duration: u64,
+    /// The id of the user that requested the song
+    requester: u64,
+}
+
+/// Writes a queue in a file format
+///
+/// ## Arguments
+///
+/// * `songs` - The songs of the queue, including the current one
+/// * `format` - The format of the file
+///
+/// ## Returns
+///
+/// * `String` - The contents of the file
+fn write_queue_file(songs: &[ExportedSong], format: QueueFileFormat) -> String {
+    let mut file = String::new();
+
+    if let QueueFileFormat::M3u = format {
+        file.push_str("#EXTM3U\n");
+    }
+
+    for song in songs {
+        match format {
+            QueueFileFormat::Urls => {}
+            QueueFileFormat::M3u => {
+                // Each entry has to fit in a single line
+                let title = song.title.replace(['\n', '\r'], " ");
+                writeln!(file, "#EXTINF:{},{title}", song.duration).unwrap();
+            }
+            QueueFileFormat::Ron => {
+                // Each song goes in its own line so errors can point at it when importing
+                let line = ron::to_string(song).unwrap();
+                writeln!(file, "{line}").unwrap();
+                continue;
+            }
+        }
+
+        writeln!(file, "{}", song.url).unwrap();
+    }
+
+    file
+}
+
+/// Parses a duration in seconds of an M3U file, where unknown durations
+/// are written as `0` or `-1`
+fn parse_seconds(seconds: &str) -> Option<Duration> {
+    seconds
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|&seconds| seconds > 0)
+        .map(Duration::from_secs)
+}
+
+/// Reads the songs of a queue file, in any of the export formats
+///
+/// ## Arguments
+///
+/// * `file` - The contents of the file
+///
+/// ## Returns
+///
+/// * `Vec<(usize, QueueEntry)>` - The line number of each song and the song, titled
+///   with its link if the file has no title for it
+/// * `Vec<InvalidLine>` - The lines that could not be read
+fn read_queue_file(file: &str) -> (Vec<(usize, QueueEntry)>, Vec<InvalidLine>) {
+    let mut entries = Vec::new();
+    let mut invalid_lines = Vec::new();
+
+    // The duration and title of the next link, from an M3U `#EXTINF` line
+    let mut next_info = None;
+
+    for (index, line) in file.lines().enumerate() {
+        let line = line.trim();
+        let number = index + 1;
+
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(directive) = line.strip_prefix('#') {
+            next_info = directive
+                .strip_prefix("EXTINF:")
+                .and_then(|info| info.split_once(','))
+                .map(|(duration, title)| (parse_seconds(duration), title.trim().to_string()));
+
+            continue;
+        }
+
+        let (info, url) = if line.starts_with('(') {
+            match ron::from_str::<ExportedSong>(line) {
+                Ok(song) => {
+                    let duration = (song.duration > 0).then(|| Duration::from_secs(song.duration));
+                    (Some((duration, song.title)), song.url)
+                }
+                Err(_) => {
+                    invalid_lines.push((number, "no es una canción en formato RON"));
+                    continue;
+                }
+            }
+        } else {
+            (next_info.take(), line.to_string())
+        };
+
+        if !is_supported_url(&url) {
+            invalid_lines.push((number, "no es un link soportado"));
+            continue;
+        }
+
+        let (duration, title) = info.unwrap_or_else(|| (None, url.clone()));
+
+        entries.push((
+            number,
+            QueueEntry {
+                title,
+                url,
+                duration,
+            },
+        ));
+    }
+
+    (entries, invalid_lines)
+}
+
+/// Uploads the queue as a file
+pub(super) async fn run_queue_export(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    format: QueueFileFormat,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let queue = {
+        let handler = handler_lock.lock().await;
+        handler.queue().current_queue()
+    };
+
+    let mut songs = Vec::new();
+
+    for track in &queue {
+        let metadata = track.metadata();
+
+        let Some(url) = metadata.source_url.clone() else {
+            continue;
+        };
+
+        songs.push(ExportedSong {
+            title: metadata.title.clone().unwrap_or_else(|| url.clone()),
+            url,
+            duration: metadata.duration.unwrap_or(Duration::ZERO).as_secs(),
+            requester: get_requester(track).await.map_or(0, |user_id| user_id.0),
+        });
+    }
+
+    if songs.is_empty() {
+        return Err(MusicCommandError::EmptyQueue.into());
+    }
+
+    let file = write_queue_file(&songs, format);
+    let filename = format!("cola.{}", format.extension());
+
+    invocation
+        .send_file(
+            ctx,
+            format!("📤 **Cola exportada con {} canciones**", songs.len()),
+            file.into_bytes(),
+            filename,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Adds the songs of a queue file to the end of the queue
+pub(super) async fn run_queue_import(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    attachment: Option<&Attachment>,
+) -> CommandResult {
+    let attachment = attachment.ok_or(MusicCommandError::NoAttachment)?;
+
+    if attachment.size > MAX_QUEUE_FILE_SIZE {
+        return Err(MusicCommandError::InvalidQueueFile.into());
+    }
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let file = attachment
+        .download()
+        .await
+        .map_err(|_| MusicCommandError::InvalidQueueFile)?;
+    let file = String::from_utf8(file).map_err(|_| MusicCommandError::InvalidQueueFile)?;
+
+    let (entries, invalid_lines) = read_queue_file(&file);
+
+    if !invalid_lines.is_empty() {
+        invocation
+            .say(ctx, invalid_lines_response(&invalid_lines))
+            .await?;
+    }
+
+    if entries.is_empty() {
+        return Err(MusicCommandError::InvalidQueueFile.into());
+    }
+
+    invocation
+        .reply(
+            ctx,
+            format!("📥 **Importando {} canciones...**", entries.len()),
+        )
+        .await?;
+
+    let mut playlist = PlaylistInsertion {
+        added: 0,
+        duration: Duration::ZERO,
+        skipped: vec![],
+    };
+
+    // Added one at a time to know the line of the songs that can't be added
+    for (number, entry) in entries {
+        let inserted = insert_entries(
+            ctx,
+            invocation.guild_id(),
+            Requester::from(invocation),
+            handler_lock.clone(),
+            [entry],
+            QueuePosition::Last,
+        )
+        .await;
+
+        playlist.added += inserted.added;
+        playlist.duration += inserted.duration;
+        playlist.skipped.extend(
+            inserted
+                .skipped
+                .into_iter()
+                .map(|title| format!("Línea {number}: {title}")),
+        );
+    }
+
+    invocation
+        .send_embed(
+            ctx,
+            playlist_added_embed(invocation.author(), &playlist),
+            |c| c,
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK: &str = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+
+    #[test]
+    fn reads_links_with_their_line_numbers() {
+        let file = format!("{LINK}\n\n  // a comment\nhttps://youtu.be/dQw4w9WgXcQ\n");
+        let (entries, invalid_lines) = read_queue_file(&file);
+
+        assert!(invalid_lines.is_empty());
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].0, 1);
+        assert_eq!(entries[0].1.url, LINK);
+        assert_eq!(entries[0].1.title, LINK);
+        assert_eq!(entries[0].1.duration, None);
+
+        assert_eq!(entries[1].0, 4);
+    }
+
+    #[test]
+    fn reads_m3u_info() {
+        let file = format!("#EXTM3U\n#EXTINF:212,A song\n{LINK}\n#EXTINF:-1,Live\n{LINK}\n{LINK}");
+        let (entries, invalid_lines) = read_queue_file(&file);
+
+        assert!(invalid_lines.is_empty());
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].0, 3);
+        assert_eq!(entries[0].1.title, "A song");
+        assert_eq!(entries[0].1.duration, Some(Duration::from_secs(212)));
+
+        assert_eq!(entries[1].1.title, "Live");
+        assert_eq!(entries[1].1.duration, None);
+
+        // The info only applies to the link after it
+        assert_eq!(entries[2].1.title, LINK);
+    }
+
+    #[test]
+    fn reads_ron_songs() {
+        let file = format!(
+            "(url: \"{LINK}\", title: \"A song\", duration: 90, requester: 1)\n\
+             (url: \"{LINK}\", title: \"Live\", duration: 0, requester: 1)"
+        );
+        let (entries, invalid_lines) = read_queue_file(&file);
+
+        assert!(invalid_lines.is_empty());
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].1.title, "A song");
+        assert_eq!(entries[0].1.duration, Some(Duration::from_secs(90)));
+        assert_eq!(entries[1].1.duration, None);
+    }
+
+    #[test]
+    fn reports_invalid_lines_by_number() {
+        let file = format!(
+            "// exported queue\n{LINK}\nnot a link\n(url: \"{LINK}\"\n\nhttps://example.com/song"
+        );
+        let (entries, invalid_lines) = read_queue_file(&file);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, 2);
+
+        assert_eq!(
+            invalid_lines,
+            vec![
+                (3, "no es un link soportado"),
+                (4, "no es una canción en formato RON"),
+                (6, "no es un link soportado"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_empty_files() {
+        for file in ["", "\n\n", "#EXTM3U\n// nothing here\n"] {
+            let (entries, invalid_lines) = read_queue_file(file);
+
+            assert!(entries.is_empty());
+            assert!(invalid_lines.is_empty());
+        }
+    }
+}
@@ -1,10 +1,11 @@
 // Responses for the music commands
 
-use std::time::Duration;
+use std::{collections::VecDeque, fmt::Write, time::Duration};
 
+use itertools::Itertools;
 use serenity::{
-    builder::CreateEmbed,
-    model::user::User,
+    builder::{CreateComponents, CreateEmbed},
+    model::{application::component::ButtonStyle, user::User},
     prelude::{Context, Mentionable},
     utils::{EmbedMessageBuilding, MessageBuilder},
 };
@@ -12,7 +13,12 @@ use songbird::tracks::TrackHandle;
 
 use crate::globals::PRIMARY_COLOR;
 
-use super::utils::{TrackChannel, TrackRequester};
+use super::{
+    filters::{source_time, AudioFilter},
+    playlists::SavedSong,
+    state::{LoopMode, MusicState},
+    utils::{FlatEntry, PlaylistInsertion, TrackChannel, TrackRequester},
+};
 
 /// Converts a duration to a string in the format `mm:ss`
 ///
@@ -48,23 +54,55 @@ fn duration_to_minutes(duration: &Duration) -> String {
 ///
 /// ## Returns
 ///
-/// * `(User, String)` - The requester and the channel name
-async fn get_custom_metadata(ctx: &Context, track: &TrackHandle) -> (User, String) {
-    let (requester_id, channel_id) = {
-        let typemap = track.typemap().read().await;
+/// * `(Option<User>, Option<String>)` - The requester and the channel name, if they can still be fetched
+async fn get_custom_metadata(ctx: &Context, track: &TrackHandle) -> (Option<User>, Option<String>) {
+    let requester = get_requester_user(ctx, track).await;
 
-        let requester_id = *typemap.get::<TrackRequester>().unwrap();
-        let channel_id = *typemap.get::<TrackChannel>().unwrap();
+    let channel_id = track.typemap().read().await.get::<TrackChannel>().copied();
 
-        (requester_id, channel_id)
+    let channel_name = match channel_id {
+        Some(channel_id) => channel_id.name(ctx).await,
+        None => None,
     };
 
-    let requester = requester_id.to_user(ctx).await.unwrap();
-    let channel_name = channel_id.name(ctx).await.unwrap();
-
     (requester, channel_name)
 }
 
+/// Returns the user that requested a track, if it can still be fetched
+async fn get_requester_user(ctx: &Context, track: &TrackHandle) -> Option<User> {
+    let requester_id = *track.typemap().read().await.get::<TrackRequester>()?;
+
+    requester_id.to_user(ctx).await.ok()
+}
+
+/// The title shown for tracks without one
+const UNKNOWN_TITLE: &str = "Sin título";
+
+/// Converts the duration of a track to a string. Livestreams and queued songs
+/// whose duration wasn't listed don't have one
+fn track_duration_to_minutes(duration: Option<&Duration>) -> String {
+    duration.map_or_else(|| "--:--".to_string(), duration_to_minutes)
+}
+
+/// Returns how much of a track is left to play, in song time. Unknown
+/// durations count as zero, and the position is left out if the track ended
+async fn time_left(track: &TrackHandle, filters: &[AudioFilter]) -> Duration {
+    let duration = track.metadata().duration.unwrap_or_default();
+
+    match track.get_info().await {
+        Ok(info) => duration.saturating_sub(source_time(filters, info.position)),
+        Err(_) => duration,
+    }
+}
+
+/// Returns the total duration of some tracks, counting unknown durations as zero
+fn queued_duration(tracks: &[TrackHandle]) -> Duration {
+    tracks
+        .iter()
+        .map(|track| track.metadata().duration.unwrap_or_default())
+        .sum()
+}
+
 /// Returns a string with a bar that represents the current position of the track.
 ///
 /// ## Arguments
@@ -78,6 +116,7 @@ async fn get_custom_metadata(ctx: &Context, track: &TrackHandle) -> (User, Strin
 fn playing_bar(length: usize, ratio: f32) -> String {
     let mut bar = String::new();
 
+    let ratio = ratio.clamp(0.0, 1.0);
     let before = (length as f32 * ratio - 1.0).round() as usize;
     let after = length - before - 1;
 
@@ -99,14 +138,12 @@ pub(super) async fn song_added_embed(
     ctx: &Context,
     queue: &[TrackHandle],
     index: usize,
+    filters: &[AudioFilter],
 ) -> CreateEmbed {
     let added_track = queue.get(index).unwrap();
 
     let metadata = added_track.metadata();
-    let title = metadata.title.as_ref().unwrap();
-    let url = metadata.source_url.as_ref().unwrap();
-    let duration = metadata.duration.as_ref().unwrap();
-    let thumbnail = metadata.thumbnail.as_ref().unwrap();
+    let title = metadata.title.as_deref().unwrap_or(UNKNOWN_TITLE);
 
     let (requester, channel_name) = get_custom_metadata(ctx, added_track).await;
 
@@ -114,32 +151,41 @@ pub(super) async fn song_added_embed(
 
     embed
         .title(format!("**{title}**"))
-        .url(url)
         .color(PRIMARY_COLOR)
-        .author(|a| a.name("Encolado").icon_url(requester.face()))
-        .thumbnail(thumbnail)
-        .field("Canal", channel_name, true)
-        .field("Duración", duration_to_minutes(duration), true);
+        .author(|a| {
+            a.name("Encolado");
+
+            if let Some(requester) = &requester {
+                a.icon_url(requester.face());
+            }
+
+            a
+        })
+        .field(
+            "Canal",
+            channel_name.unwrap_or_else(|| "Desconocido".to_string()),
+            true,
+        )
+        .field(
+            "Duración",
+            track_duration_to_minutes(metadata.duration.as_ref()),
+            true,
+        );
+
+    if let Some(url) = &metadata.source_url {
+        embed.url(url);
+    }
+
+    if let Some(thumbnail) = &metadata.thumbnail {
+        embed.thumbnail(thumbnail);
+    }
 
     if index > 0 {
         embed.field("Posición", index, true);
 
-        let first_track_time_left = {
-            let first_track = queue.first().unwrap();
-            let first_track_duration = first_track.metadata().duration.unwrap();
-            let first_track_position = first_track.get_info().await.unwrap().position;
-
-            first_track_duration - first_track_position
-        };
+        let first_track_time_left = time_left(queue.first().unwrap(), filters).await;
 
-        let time_to_play = queue
-            .iter()
-            .skip(1)
-            .take(index - 1)
-            .fold(Duration::new(0, 0), |acc, track| {
-                acc + track.metadata().duration.unwrap()
-            })
-            + first_track_time_left;
+        let time_to_play = queued_duration(&queue[1..index]) + first_track_time_left;
 
         embed.field(
             "Tiempo hasta que toque",
@@ -151,6 +197,129 @@ pub(super) async fn song_added_embed(
     embed
 }
 
+pub(super) fn playlist_added_embed(requester: &User, playlist: &PlaylistInsertion) -> CreateEmbed {
+    let added = playlist.added;
+
+    let mut embed = CreateEmbed::default();
+
+    embed
+        .title(format!("**{added} canciones agregadas**"))
+        .color(PRIMARY_COLOR)
+        .author(|a| a.name("Playlist encolada").icon_url(requester.face()))
+        .field(
+            "Duración total",
+            duration_to_minutes(&playlist.duration),
+            true,
+        );
+
+    if !playlist.skipped.is_empty() {
+        let count = playlist.skipped.len();
+
+        // Embed fields can't be longer than 1024 characters
+        let mut skipped = String::new();
+
+        for (i, title) in playlist.skipped.iter().enumerate() {
+            let line = format!("• {title}\n");
+
+            if skipped.len() + line.len() > 950 {
+                skipped.push_str(&format!("...y {} más", count - i));
+                break;
+            }
+
+            skipped.push_str(&line);
+        }
+
+        embed.field(format!("No disponibles ({count})"), skipped, false);
+    }
+
+    embed
+}
+
+pub(super) fn playlist_list_embed(playlists: &[(String, usize)]) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+
+    embed.title("Playlists guardadas").color(PRIMARY_COLOR);
+
+    if playlists.is_empty() {
+        embed.description("No hay ninguna playlist guardada");
+        return embed;
+    }
+
+    let mut description = MessageBuilder::new();
+
+    for (name, count) in playlists {
+        description
+            .push_mono_safe(name)
+            .push_line(format!(" | {count} canciones"));
+    }
+
+    embed.description(description);
+
+    embed
+}
+
+pub(super) fn playlist_show_embed(name: &str, songs: &[SavedSong]) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+
+    embed
+        .title(format!("Playlist: {name}"))
+        .color(PRIMARY_COLOR)
+        .footer(|f| f.text(format!("{} canciones", songs.len())));
+
+    // Embed descriptions can't be longer than 4096 characters
+    let mut description = String::new();
+
+    for (i, song) in songs.iter().enumerate() {
+        let index = i + 1;
+        let line = MessageBuilder::new()
+            .push_bold(format!("{index}. "))
+            .push_named_link_safe(&song.title, &song.url)
+            .push_line("")
+            .build();
+
+        if description.len() + line.len() > 4000 {
+            description.push_str(&format!("...y {} más", songs.len() - i));
+            break;
+        }
+
+        description.push_str(&line);
+    }
+
+    embed.description(description);
+
+    embed
+}
+
+pub(super) fn search_results_embed(query: &str, results: &[FlatEntry]) -> CreateEmbed {
+    let mut description = MessageBuilder::new();
+
+    for (i, entry) in results.iter().enumerate() {
+        let index = i + 1;
+        let duration = entry
+            .duration
+            .map(|seconds| duration_to_minutes(&Duration::from_secs_f64(seconds)))
+            .unwrap_or_else(|| "?".to_string());
+
+        description
+            .push_bold(format!("{index}. "))
+            .push_named_link_safe(entry.display_title(), &entry.url)
+            .push_line("")
+            .push_safe(entry.display_uploader())
+            .push(" | ")
+            .push_mono_line_safe(duration);
+    }
+
+    let mut embed = CreateEmbed::default();
+
+    embed
+        .title(format!("Resultados para: {query}"))
+        .color(PRIMARY_COLOR)
+        .description(description)
+        .footer(|f| f.text("Respondé con un número o tocá un botón"));
+
+    embed
+}
+
 pub(super) fn song_skipped_response(track: &TrackHandle) -> String {
     let metadata = track.metadata();
     let title = metadata.title.as_ref().unwrap();
@@ -161,21 +330,201 @@ pub(super) fn song_skipped_response(track: &TrackHandle) -> String {
         .build()
 }
 
-pub(super) fn song_seeked_response(position: Duration) -> String {
+/// The maximum amount of removed songs listed by title
+const REMOVED_LIST_SIZE: usize = 10;
+
+/// The maximum length of a title in the list of removed songs
+const REMOVED_TITLE_LENGTH: usize = 60;
+
+/// Shortens a title to `max_length` characters, ending it with an ellipsis if it was cut
+fn truncate_title(title: &str, max_length: usize) -> String {
+    if title.chars().count() <= max_length {
+        return title.to_string();
+    }
+
+    let truncated = title.chars().take(max_length - 1).collect::<String>();
+
+    format!("{}…", truncated.trim_end())
+}
+
+pub(super) fn songs_removed_response(removed: &[TrackHandle]) -> String {
+    let title_of = |track: &TrackHandle| {
+        let title = track.metadata().title.clone().unwrap_or_default();
+        truncate_title(&title, REMOVED_TITLE_LENGTH)
+    };
+
+    let mut response = MessageBuilder::new();
+
+    if let [track] = removed {
+        return response
+            .push("🗑️ ")
+            .push_bold_safe(title_of(track))
+            .push(" fue eliminada de la cola")
+            .build();
+    }
+
+    response.push_bold(format!(
+        "🗑️ {} canciones eliminadas de la cola:",
+        removed.len()
+    ));
+
+    for track in removed.iter().take(REMOVED_LIST_SIZE) {
+        response.push("\n• ").push_safe(title_of(track));
+    }
+
+    if removed.len() > REMOVED_LIST_SIZE {
+        response
+            .push("\n")
+            .push_italic(format!("...y {} más", removed.len() - REMOVED_LIST_SIZE));
+    }
+
+    response.build()
+}
+
+/// The maximum amount of invalid lines listed when importing a queue
+const INVALID_LINES_LIST_SIZE: usize = 10;
+
+pub(super) fn invalid_lines_response(invalid_lines: &[(usize, &str)]) -> String {
+    let mut response = MessageBuilder::new();
+
+    response.push_bold(format!(
+        "⚠️ {} líneas no se pudieron leer:",
+        invalid_lines.len()
+    ));
+
+    for (number, problem) in invalid_lines.iter().take(INVALID_LINES_LIST_SIZE) {
+        response.push(format!("\n• Línea {number}: {problem}"));
+    }
+
+    if invalid_lines.len() > INVALID_LINES_LIST_SIZE {
+        response.push("\n").push_italic(format!(
+            "...y {} más",
+            invalid_lines.len() - INVALID_LINES_LIST_SIZE
+        ));
+    }
+
+    response.build()
+}
+
+pub(super) fn skipped_to_response(target: &TrackHandle, skipped: usize) -> String {
+    let metadata = target.metadata();
+    let title = metadata.title.as_ref().unwrap();
+
+    let skipped = match skipped {
+        1 => "1 canción salteada".to_string(),
+        skipped => format!("{skipped} canciones salteadas"),
+    };
+
     MessageBuilder::new()
         .push_bold_safe("⏩ Saltando a: ")
-        .push_mono_safe(duration_to_minutes(&position))
+        .push_mono_safe(title)
+        .push(format!(" ({skipped})"))
+        .build()
+}
+
+pub(super) fn skip_vote_response(votes: usize, required: usize) -> String {
+    MessageBuilder::new()
+        .push_bold_safe("🗳️ Votos para skippear: ")
+        .push(format!("{votes}/{required}"))
         .build()
 }
 
-pub(super) async fn now_playing_embed(ctx: &Context, track: &TrackHandle) -> CreateEmbed {
+pub(super) fn song_previous_response(track: &TrackHandle) -> String {
     let metadata = track.metadata();
     let title = metadata.title.as_ref().unwrap();
-    let url = metadata.source_url.as_ref().unwrap();
-    let duration = metadata.duration.as_ref().unwrap();
-    let thumbnail = metadata.thumbnail.as_ref().unwrap();
 
-    let (requester, _) = get_custom_metadata(ctx, track).await;
+    MessageBuilder::new()
+        .push_bold_safe("⏮️ **Volviendo a**: ")
+        .push_mono_safe(title)
+        .build()
+}
+
+pub(super) fn song_seeked_response(old_position: Duration, new_position: Duration) -> String {
+    let icon = if new_position < old_position {
+        "⏪"
+    } else {
+        "⏩"
+    };
+
+    MessageBuilder::new()
+        .push_bold_safe(format!("{icon} Saltando: "))
+        .push_mono_safe(duration_to_minutes(&old_position))
+        .push(" → ")
+        .push_mono_safe(duration_to_minutes(&new_position))
+        .build()
+}
+
+pub(super) fn loop_mode_response(loop_mode: LoopMode) -> String {
+    MessageBuilder::new()
+        .push_bold_safe("Loop: ")
+        .push(loop_mode)
+        .build()
+}
+
+pub(super) fn fair_queue_response(enabled: bool) -> String {
+    let status = if enabled { "activado" } else { "desactivado" };
+
+    MessageBuilder::new()
+        .push_bold_safe("⚖️ Modo justo: ")
+        .push(status)
+        .build()
+}
+
+pub(super) fn volume_response(volume: u32) -> String {
+    let icon = match volume {
+        0 => "🔇",
+        1..=50 => "🔈",
+        51..=100 => "🔉",
+        _ => "🔊",
+    };
+
+    MessageBuilder::new()
+        .push_bold_safe(format!("{icon} Volumen: "))
+        .push(format!("{volume}%"))
+        .build()
+}
+
+pub(super) fn filters_response(filters: &[AudioFilter]) -> String {
+    let mut response = MessageBuilder::new();
+    response.push_bold_safe("🎛️ Filtros: ");
+
+    if filters.is_empty() {
+        response.push("ninguno");
+    } else {
+        response.push(filters.iter().join(", "));
+    }
+
+    response.build()
+}
+
+pub(super) fn autoplay_response(enabled: bool) -> String {
+    let status = if enabled { "activado" } else { "desactivado" };
+
+    MessageBuilder::new()
+        .push_bold_safe("📻 Autoplay: ")
+        .push(status)
+        .build()
+}
+
+/// Returns how to show who requested a track, marking the songs added by autoplay
+fn requester_label(requester: &User, name: String) -> String {
+    if requester.bot {
+        format!("📻 Autoplay ({name})")
+    } else {
+        name
+    }
+}
+
+pub(super) async fn now_playing_embed(
+    ctx: &Context,
+    track: &TrackHandle,
+    next: Option<&TrackHandle>,
+    state: &MusicState,
+) -> CreateEmbed {
+    let metadata = track.metadata();
+    let title = metadata.title.as_deref().unwrap_or(UNKNOWN_TITLE);
+
+    let requester = get_requester_user(ctx, track).await;
 
     let mut embed = CreateEmbed::default();
 
@@ -185,52 +534,121 @@ pub(super) async fn now_playing_embed(ctx: &Context, track: &TrackHandle) -> Cre
                 .icon_url(ctx.cache.current_user().face())
         })
         .title(title)
-        .url(url)
-        .color(PRIMARY_COLOR)
-        .thumbnail(thumbnail);
+        .color(PRIMARY_COLOR);
 
-    let track_position = track.get_info().await.unwrap().position;
+    if let Some(url) = &metadata.source_url {
+        embed.url(url);
+    }
 
-    let playing_bar = playing_bar(30, track_position.as_secs_f32() / duration.as_secs_f32());
+    if let Some(thumbnail) = &metadata.thumbnail {
+        embed.thumbnail(thumbnail);
+    }
+
+    // The track may have ended while the embed is built
+    let track_info = track.get_info().await.ok();
+    let track_position = track_info.as_ref().map_or(Duration::ZERO, |info| {
+        source_time(&state.filters, info.position)
+    });
+
+    // Livestreams have no duration, so their bar stays at the start
+    let ratio = metadata.duration.map_or(0.0, |duration| {
+        track_position.as_secs_f32() / duration.as_secs_f32()
+    });
+
+    let playing_bar = playing_bar(30, ratio);
     let parsed_duration = format!(
         "{} / {}",
         duration_to_minutes(&track_position),
-        duration_to_minutes(duration)
+        track_duration_to_minutes(metadata.duration.as_ref())
+    );
+    let requester_name = requester.map_or_else(
+        || "Desconocido".to_string(),
+        |requester| requester_label(&requester, requester.name.clone()),
     );
-    let requester_name = requester.name;
 
-    embed.description(format!(
-        "\n`{playing_bar}`\n\n`{parsed_duration}`\n\n**Pedida por:** {requester_name}"
-    ));
+    let mut description =
+        format!("\n`{playing_bar}`\n\n`{parsed_duration}`\n\n**Pedida por:** {requester_name}");
+
+    if let Some(info) = track_info {
+        let volume = (info.volume * 100.0).round();
+        write!(description, "\n**Volumen:** {volume}%").unwrap();
+    }
+
+    embed.description(description);
+
+    if !state.filters.is_empty() {
+        let filters = state.filters.iter().join(", ");
+        embed.field("Filtros", filters, false);
+    }
+
+    if let Some(next) = next {
+        embed.field("A continuación", queue_item(ctx, next).await, false);
+    }
+
+    if state.loop_mode != LoopMode::Off {
+        embed.footer(|f| f.text(format!("Loop: {}", state.loop_mode)));
+    }
 
     embed
 }
 
 async fn queue_item(ctx: &Context, track: &TrackHandle) -> String {
     let metadata = track.metadata();
-    let title = metadata.title.as_ref().unwrap();
-    let url = metadata.source_url.as_ref().unwrap();
-    let duration = metadata.duration.as_ref().unwrap();
+    let title = metadata.title.as_deref().unwrap_or(UNKNOWN_TITLE);
 
-    let (requester, _) = get_custom_metadata(ctx, track).await;
-    let requester_mention = requester.mention();
+    let requester_mention = match get_requester_user(ctx, track).await {
+        Some(requester) => requester_label(&requester, requester.mention().to_string()),
+        None => "Desconocido".to_string(),
+    };
 
-    MessageBuilder::new()
-        .push_named_link_safe(title, url)
-        .push(" | ")
-        .push_mono_safe(duration_to_minutes(duration))
+    let mut item = MessageBuilder::new();
+
+    match &metadata.source_url {
+        Some(url) => item.push_named_link_safe(title, url),
+        None => item.push_safe(title),
+    };
+
+    item.push(" | ")
+        .push_mono_safe(track_duration_to_minutes(metadata.duration.as_ref()))
         .push(" | ")
         .push_bold_safe(format!("Pedida por: {requester_mention}"))
         .build()
 }
 
-pub(super) async fn queue_embed(ctx: &Context, queue: &Vec<TrackHandle>) -> CreateEmbed {
+/// The amount of upcoming tracks shown in each page of the queue
+const QUEUE_PAGE_SIZE: usize = 10;
+
+/// Returns the amount of pages needed to show a queue
+///
+/// ## Arguments
+///
+/// * `queue_length` - The amount of tracks in the queue, including the current one
+///
+/// ## Returns
+///
+/// * `usize` - The amount of pages, at least 1
+pub(super) fn queue_page_count(queue_length: usize) -> usize {
+    queue_length
+        .saturating_sub(1)
+        .div_ceil(QUEUE_PAGE_SIZE)
+        .max(1)
+}
+
+pub(super) async fn queue_embed(
+    ctx: &Context,
+    queue: &[TrackHandle],
+    state: &MusicState,
+    page: usize,
+) -> CreateEmbed {
     let mut embed = CreateEmbed::default();
 
     embed.title("Cola de música").colour(PRIMARY_COLOR);
 
     let (first, rest) = queue.split_first().unwrap();
 
+    let pages = queue_page_count(queue.len());
+    let page = page.clamp(1, pages);
+
     let mut description = MessageBuilder::new();
 
     description
@@ -240,7 +658,12 @@ pub(super) async fn queue_embed(ctx: &Context, queue: &Vec<TrackHandle>) -> Crea
     if !rest.is_empty() {
         description.push_underline_line("Próximas:");
 
-        for (i, track) in rest.iter().enumerate() {
+        for (i, track) in rest
+            .iter()
+            .enumerate()
+            .skip((page - 1) * QUEUE_PAGE_SIZE)
+            .take(QUEUE_PAGE_SIZE)
+        {
             let item = queue_item(ctx, track).await;
             let index = i + 1;
             description.push_line(format!("**{index}.** {item}"));
@@ -251,21 +674,108 @@ pub(super) async fn queue_embed(ctx: &Context, queue: &Vec<TrackHandle>) -> Crea
 
     let count = queue.len();
 
-    let first_duration = first.metadata().duration.unwrap();
-    let first_position = first.get_info().await.unwrap().position;
-    let remaining_duration = first_duration - first_position;
-
-    let queue_duration = rest.iter().fold(Duration::new(0, 0), |acc, track| {
-        acc + track.metadata().duration.unwrap()
-    });
-    let total_duration = remaining_duration + queue_duration;
+    let total_duration = time_left(first, &state.filters).await + queued_duration(rest);
     let total_duration = duration_to_minutes(&total_duration);
 
-    embed.footer(|f| {
-        f.text(format!(
-            "{count} canciones en la cola | Duración total: {total_duration}"
-        ))
-    });
+    let mut footer = format!(
+        "Página {page} de {pages} | {count} canciones en la cola | Duración total: {total_duration}"
+    );
+
+    if state.loop_mode != LoopMode::Off {
+        footer.push_str(&format!(" | Loop: {}", state.loop_mode));
+    }
+
+    embed.footer(|f| f.text(footer));
 
     embed
 }
+
+pub(super) fn queue_page_buttons(
+    components: &mut CreateComponents,
+    page: usize,
+    pages: usize,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id("prev")
+                .emoji('◀')
+                .style(ButtonStyle::Secondary)
+                .disabled(page <= 1)
+        })
+        .create_button(|b| {
+            b.custom_id("next")
+                .emoji('▶')
+                .style(ButtonStyle::Secondary)
+                .disabled(page >= pages)
+        })
+    })
+}
+
+/// The amount of tracks shown in each page of the history
+const HISTORY_PAGE_SIZE: usize = 10;
+
+pub(super) async fn history_embed(
+    ctx: &Context,
+    history: &VecDeque<TrackHandle>,
+    page: usize,
+) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+
+    embed.title("Historial").colour(PRIMARY_COLOR);
+
+    let count = history.len();
+    let pages = count.div_ceil(HISTORY_PAGE_SIZE).max(1);
+    let page = page.clamp(1, pages);
+
+    let mut description = MessageBuilder::new();
+
+    // The most recent tracks go first
+    for (i, track) in history
+        .iter()
+        .rev()
+        .enumerate()
+        .skip((page - 1) * HISTORY_PAGE_SIZE)
+        .take(HISTORY_PAGE_SIZE)
+    {
+        let item = queue_item(ctx, track).await;
+        let index = i + 1;
+        description.push_line(format!("**{index}.** {item}"));
+    }
+
+    embed.description(description);
+
+    embed.footer(|f| f.text(format!("Página {page} de {pages} | {count} canciones")));
+
+    embed
+}
+
+/// Formats a size in bytes as megabytes
+fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+pub(super) fn cache_stats_response(
+    hits: u64,
+    misses: u64,
+    songs: usize,
+    size: u64,
+    max_size: u64,
+) -> String {
+    let lookups = hits + misses;
+    let hit_rate = if lookups == 0 {
+        0.0
+    } else {
+        hits as f64 / lookups as f64 * 100.0
+    };
+
+    MessageBuilder::new()
+        .push_bold_line("💽 Caché de audio")
+        .push_line(format!("Aciertos: {hits} ({hit_rate:.1}%)"))
+        .push_line(format!("Fallos: {misses}"))
+        .push(format!(
+            "Tamaño: {} de {} ({songs} canciones)",
+            format_megabytes(size),
+            format_megabytes(max_size)
+        ))
+        .build()
+}
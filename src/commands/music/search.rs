@@ -0,0 +1,149 @@
+// Interactive search
+
+use std::time::{Duration, Instant};
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{
+        application::{component::ButtonStyle, interaction::InteractionResponseType},
+        channel::Message,
+    },
+    prelude::Context,
+};
+
+use super::{
+    controls::player_buttons,
+    errors::MusicCommandError,
+    responses::{search_results_embed, searching_response, song_added_embed},
+    state::get_music_state,
+    utils::{
+        get_handler_lock, insert_song, search_song, search_songs, Invocation, QueuePosition,
+        Requester,
+    },
+};
+
+/// The amount of results shown by the `search` command
+const SEARCH_RESULTS: usize = 5;
+
+/// The time the requester has to pick a result
+const PICK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/////////////////////////
+//      Commands       //
+/////////////////////////
+
+#[command]
+#[only_in(guilds)]
+#[aliases("buscar", "find")]
+pub async fn search(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    run_search(ctx, &msg.into(), args.rest()).await
+}
+
+/// Shows the results of a search and adds the one the user picks
+pub(super) async fn run_search(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    query: &str,
+) -> CommandResult {
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    if query.is_empty() {
+        return Err(MusicCommandError::FailedVideoSearch.into());
+    }
+
+    invocation.reply(ctx, searching_response(query)).await?;
+    let results = search_songs(query, SEARCH_RESULTS).await?;
+    let count = results.len();
+
+    let mut picker = invocation
+        .send_embed(ctx, search_results_embed(query, &results), |c| {
+            c.create_action_row(|row| {
+                for i in 1..=count {
+                    row.create_button(|b| {
+                        b.custom_id(i.to_string())
+                            .label(i.to_string())
+                            .style(ButtonStyle::Primary)
+                    });
+                }
+                row
+            })
+        })
+        .await?;
+
+    let deadline = Instant::now() + PICK_TIMEOUT;
+
+    // Only the requester can pick, either by replying or by clicking a button
+    let choice = tokio::select! {
+        reply = invocation
+            .channel_id()
+            .await_reply(ctx)
+            .author_id(invocation.author().id)
+            .filter(move |reply| {
+                matches!(reply.content.trim().parse::<usize>(), Ok(n) if (1..=count).contains(&n))
+            })
+            .timeout(PICK_TIMEOUT) => {
+            reply.and_then(|reply| reply.content.trim().parse::<usize>().ok())
+        }
+        clicked = async {
+            while let Some(interaction) = picker
+                .await_component_interaction(ctx)
+                .timeout(deadline.saturating_duration_since(Instant::now()))
+                .await
+            {
+                if interaction.user.id != invocation.author().id {
+                    interaction
+                        .create_interaction_response(&ctx.http, |r| {
+                            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                                .interaction_response_data(|d| {
+                                    d.content("Solo quien hizo la búsqueda puede elegir")
+                                        .ephemeral(true)
+                                })
+                        })
+                        .await?;
+
+                    continue;
+                }
+
+                interaction
+                    .create_interaction_response(&ctx.http, |r| {
+                        r.kind(InteractionResponseType::DeferredUpdateMessage)
+                    })
+                    .await?;
+
+                return Ok(interaction.data.custom_id.parse::<usize>().ok());
+            }
+
+            Ok::<_, serenity::Error>(None)
+        } => clicked?,
+    };
+
+    picker.edit(ctx, |m| m.components(|c| c)).await?;
+
+    let Some(entry) = choice.and_then(|n| results.get(n - 1)) else {
+        invocation
+            .reply(ctx, "⌛ **Se acabó el tiempo para elegir**")
+            .await?;
+        return Ok(());
+    };
+
+    let (source, start) = search_song(ctx, invocation.guild_id(), &entry.url).await?;
+
+    let position = insert_song(
+        ctx,
+        invocation.guild_id(),
+        Requester::from(invocation),
+        handler_lock.clone(),
+        source.into(),
+        start,
+        QueuePosition::Last,
+    )
+    .await?;
+
+    let queue = handler_lock.lock().await.queue().current_queue();
+    let filters = get_music_state(ctx, invocation.guild_id()).await.filters;
+    let embed = song_added_embed(ctx, &queue, position, &filters).await;
+
+    invocation.send_embed(ctx, embed, player_buttons).await?;
+
+    Ok(())
+}
@@ -0,0 +1,457 @@
+// Queue persistence across restarts
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::{self, File},
+    io::BufReader,
+    sync::Arc,
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    framework::standard::{macros::command, CommandResult},
+    model::{
+        channel::Message,
+        prelude::{ChannelId, GuildId, UserId},
+    },
+    prelude::{Context, Mutex, TypeMapKey},
+};
+use songbird::{tracks::TrackQueue, Call, Event, EventContext, EventHandler};
+
+use super::{
+    errors::MusicCommandError,
+    filters::{lazy_source, source_time, AudioFilter},
+    state::get_music_state,
+    utils::{
+        get_handler_lock, insert_song, join_voice_channel, Invocation, QueueEntry, QueuePosition,
+        Requester, TrackChannel, TrackRequester,
+    },
+};
+
+/// A track saved in a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedTrack {
+    pub url: String,
+    pub requester: u64,
+    pub channel: u64,
+    /// The title, missing in sessions saved before it was stored
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The duration in seconds
+    #[serde(default)]
+    pub duration: Option<u64>,
+}
+
+/// A snapshot of a guild's queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub tracks: Vec<SavedTrack>,
+    /// The position of the first track, in seconds
+    pub position: u64,
+    /// The voice channel the bot was playing in, joined again on startup
+    #[serde(default)]
+    pub voice_channel: Option<u64>,
+}
+
+/// The collection of saved sessions for the bot.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Sessions {
+    sessions: HashMap<u64, SavedSession>,
+
+    /// Guilds whose session is being restored. Snapshots are not taken for
+    /// these guilds until the restore finishes.
+    #[serde(skip)]
+    restoring: HashSet<u64>,
+}
+
+impl Sessions {
+    /// Get the saved session for a guild.
+    pub fn get(&self, id: &GuildId) -> Option<&SavedSession> {
+        self.sessions.get(&id.0)
+    }
+
+    /// Replace the saved session for a guild. An empty session removes it.
+    ///
+    /// Returns `true` if the stored session changed.
+    pub fn set(&mut self, id: &GuildId, session: SavedSession) -> bool {
+        if session.tracks.is_empty() {
+            return self.sessions.remove(&id.0).is_some();
+        }
+
+        if self.sessions.get(&id.0) == Some(&session) {
+            return false;
+        }
+
+        self.sessions.insert(id.0, session);
+        true
+    }
+
+    /// Remove the saved session for a guild.
+    ///
+    /// Returns `true` if there was a session.
+    pub fn remove(&mut self, id: &GuildId) -> bool {
+        self.sessions.remove(&id.0).is_some()
+    }
+
+    /// Try to load sessions from the file specified in the `SESSIONS_PATH` environment variable.
+    ///
+    /// Returns `None` if the environment variable is not set or the file could not be opened.
+    pub fn try_load() -> Option<Self> {
+        let path = env::var("SESSIONS_PATH").ok()?;
+        let file = File::open(&path).ok()?;
+        let reader = BufReader::new(file);
+        let sessions = ron::de::from_reader(reader).ok()?;
+        println!("Loaded sessions from {path}");
+        Some(sessions)
+    }
+
+    /// Get the saved sessions that can be restored on startup.
+    fn restorable(&self) -> Vec<(GuildId, ChannelId)> {
+        self.sessions
+            .iter()
+            .filter_map(|(&guild_id, session)| {
+                Some((GuildId(guild_id), ChannelId(session.voice_channel?)))
+            })
+            .collect()
+    }
+}
+
+impl TypeMapKey for Sessions {
+    type Value = Self;
+}
+
+lazy_static! {
+    /// Held while the sessions file is written, so an older snapshot
+    /// never overwrites a newer one
+    static ref SAVE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Save the sessions to the file specified in the `SESSIONS_PATH` environment variable.
+/// The file is written without holding the lock to the bot data.
+///
+/// Does nothing if the environment variable is not set.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+async fn save_sessions(ctx: &Context) -> Result<(), MusicCommandError> {
+    let Ok(path) = env::var("SESSIONS_PATH") else {
+        return Ok(());
+    };
+
+    let _guard = SAVE_LOCK.lock().await;
+
+    let contents = {
+        let data = ctx.data.read().await;
+        let Some(sessions) = data.get::<Sessions>() else {
+            return Ok(());
+        };
+
+        ron::ser::to_string_pretty(sessions, ron::ser::PrettyConfig::default())
+            .map_err(|_| MusicCommandError::SessionSaveFailed)?
+    };
+
+    tokio::task::spawn_blocking(move || fs::write(path, contents))
+        .await
+        .map_err(|_| MusicCommandError::SessionSaveFailed)?
+        .map_err(|_| MusicCommandError::SessionSaveFailed)
+}
+
+/// Periodically snapshots the queue of a guild into the saved sessions
+pub(super) struct SessionHandler {
+    pub(super) ctx: Context,
+    pub(super) guild_id: GuildId,
+    /// The voice channel the queue plays in
+    pub(super) voice_channel: ChannelId,
+    pub(super) queue: TrackQueue,
+}
+
+#[async_trait]
+impl EventHandler for SessionHandler {
+    async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
+        let filters = get_music_state(&self.ctx, self.guild_id).await.filters;
+        let mut session = snapshot_queue(&self.queue, &filters).await;
+        session.voice_channel = Some(self.voice_channel.0);
+
+        let changed = {
+            let mut data = self.ctx.data.write().await;
+            let sessions = data.get_mut::<Sessions>()?;
+
+            !sessions.restoring.contains(&self.guild_id.0) && sessions.set(&self.guild_id, session)
+        };
+
+        if changed {
+            if let Err(why) = save_sessions(&self.ctx).await {
+                println!("Error saving sessions: {why:?}");
+            }
+        }
+
+        None
+    }
+}
+
+/// Takes a snapshot of the given queue
+///
+/// ## Arguments
+///
+/// * `queue` - The queue to snapshot
+/// * `filters` - The active filters, to save the position in the current song
+///
+/// ## Returns
+///
+/// * `SavedSession` - The tracks in the queue and the position of the current one
+async fn snapshot_queue(queue: &TrackQueue, filters: &[AudioFilter]) -> SavedSession {
+    let mut tracks = vec![];
+
+    for track in queue.current_queue() {
+        let Some(url) = track.metadata().source_url.clone() else {
+            continue;
+        };
+
+        let typemap = track.typemap().read().await;
+
+        let (Some(requester), Some(channel)) = (
+            typemap.get::<TrackRequester>(),
+            typemap.get::<TrackChannel>(),
+        ) else {
+            continue;
+        };
+
+        tracks.push(SavedTrack {
+            url,
+            requester: requester.0,
+            channel: channel.0,
+            title: track.metadata().title.clone(),
+            duration: track.metadata().duration.map(|duration| duration.as_secs()),
+        });
+    }
+
+    let position = match queue.current() {
+        Some(track) => track
+            .get_info()
+            .await
+            .map(|info| source_time(filters, info.position).as_secs())
+            .unwrap_or(0),
+        None => 0,
+    };
+
+    SavedSession {
+        tracks,
+        position,
+        voice_channel: None,
+    }
+}
+
+/// Marks a guild as being restored (or not), pausing its snapshots
+async fn set_restoring(ctx: &Context, guild_id: GuildId, restoring: bool) {
+    let mut data = ctx.data.write().await;
+
+    if let Some(sessions) = data.get_mut::<Sessions>() {
+        if restoring {
+            sessions.restoring.insert(guild_id.0);
+        } else {
+            sessions.restoring.remove(&guild_id.0);
+        }
+    }
+}
+
+/// Adds the tracks of a saved session to the queue. The songs are loaded
+/// when they start, and the first one picks up where it was left.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the session belongs to
+/// * `handler_lock` - A lock to the songbird handler
+/// * `session` - The session to restore
+///
+/// ## Returns
+///
+/// * `usize` - The number of tracks restored
+async fn restore_session(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+    session: &SavedSession,
+) -> usize {
+    set_restoring(ctx, guild_id, true).await;
+
+    let mut restored = 0;
+
+    for (i, saved) in session.tracks.iter().enumerate() {
+        let entry = QueueEntry {
+            title: saved.title.clone().unwrap_or_else(|| saved.url.clone()),
+            url: saved.url.clone(),
+            duration: saved.duration.map(Duration::from_secs),
+        };
+
+        let Ok(source) = lazy_source(ctx, guild_id, entry.url.clone(), entry.metadata()).await
+        else {
+            println!("Could not restore {}", saved.url);
+            continue;
+        };
+
+        // Pick up the first track where it was left
+        let start = (i == 0 && session.position > 0).then(|| Duration::from_secs(session.position));
+
+        let inserted = insert_song(
+            ctx,
+            guild_id,
+            Requester {
+                user_id: UserId(saved.requester),
+                channel_id: ChannelId(saved.channel),
+            },
+            handler_lock.clone(),
+            source.into(),
+            start,
+            QueuePosition::Last,
+        )
+        .await;
+
+        if inserted.is_ok() {
+            restored += 1;
+        }
+    }
+
+    set_restoring(ctx, guild_id, false).await;
+
+    restored
+}
+
+/// Restores the saved session of a guild if nothing is queued, announcing
+/// it in the text channel the first song was requested in
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the session belongs to
+/// * `handler_lock` - A lock to the songbird handler
+pub(super) async fn restore_saved_session(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+) {
+    let session = {
+        let data = ctx.data.read().await;
+        data.get::<Sessions>()
+            .and_then(|sessions| sessions.get(&guild_id).cloned())
+    };
+
+    let Some(session) = session else {
+        return;
+    };
+
+    // Announced in the text channel the first song was requested in
+    let Some(first) = session.tracks.first() else {
+        return;
+    };
+    let channel_id = ChannelId(first.channel);
+
+    if !handler_lock.lock().await.queue().is_empty() {
+        return;
+    }
+
+    let count = session.tracks.len();
+    let restored = restore_session(ctx, guild_id, handler_lock, &session).await;
+
+    if let Err(why) = channel_id
+        .say(
+            &ctx.http,
+            format!("♻️ **{restored} de {count} canciones restauradas de la sesión anterior**"),
+        )
+        .await
+    {
+        println!("Could not announce the restored session: {why:?}");
+    }
+}
+
+/// Forgets the saved session of a guild, so it isn't restored when the bot
+/// starts again
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the session belongs to
+pub(super) async fn clear_saved_session(ctx: &Context, guild_id: GuildId) {
+    let removed = {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<Sessions>()
+            .is_some_and(|sessions| sessions.remove(&guild_id))
+    };
+
+    if removed {
+        if let Err(why) = save_sessions(ctx).await {
+            println!("Error saving sessions: {why:?}");
+        }
+    }
+}
+
+/// Joins the voice channels of the saved sessions and restores their queues.
+/// Used when the bot starts.
+pub async fn restore_sessions(ctx: &Context) {
+    let restorable = {
+        let data = ctx.data.read().await;
+        data.get::<Sessions>()
+            .map(Sessions::restorable)
+            .unwrap_or_default()
+    };
+
+    for (guild_id, channel_id) in restorable {
+        let ctx = ctx.clone();
+
+        tokio::spawn(async move {
+            match join_voice_channel(&ctx, guild_id, channel_id).await {
+                Ok(handler_lock) => restore_saved_session(&ctx, guild_id, handler_lock).await,
+                Err(why) => println!("Could not rejoin {channel_id} in {guild_id}: {why:?}"),
+            }
+        });
+    }
+}
+
+/////////////////////////
+//      Commands       //
+/////////////////////////
+
+#[command]
+#[only_in(guilds)]
+#[aliases("restore")]
+pub async fn resume_session(ctx: &Context, msg: &Message) -> CommandResult {
+    run_resume_session(ctx, &msg.into()).await
+}
+
+/// Adds back the songs that were in the queue when the bot stopped
+pub(super) async fn run_resume_session(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+) -> CommandResult {
+    let guild_id = invocation.guild_id();
+
+    let session = {
+        let data = ctx.data.read().await;
+        data.get::<Sessions>()
+            .and_then(|sessions| sessions.get(&guild_id).cloned())
+            .ok_or(MusicCommandError::NoSavedSession)?
+    };
+
+    let handler_lock = get_handler_lock(ctx, invocation).await?;
+
+    let count = session.tracks.len();
+    invocation
+        .reply(ctx, format!("♻️ **Restaurando {count} canciones...**"))
+        .await?;
+
+    let restored = restore_session(ctx, guild_id, handler_lock, &session).await;
+
+    invocation
+        .say(
+            ctx,
+            format!("✅ **{restored} de {count} canciones restauradas**"),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -1,18 +1,116 @@
 use l0c0b0t_macros::define_setting;
 use serenity::{
     framework::standard::{macros::check, Args, CommandOptions, Reason},
-    model::prelude::{ChannelId, GuildId, Message},
+    model::prelude::{ChannelId, GuildId, Message, RoleId, UserId},
     prelude::{Context, Mentionable},
 };
 
-use crate::utils::OptionalChannel;
+use crate::{
+    framework::{
+        handler::get_handler,
+        settings::{Setting, SettingsError},
+        utils::handle_error,
+    },
+    utils::{DefaultOn, OptionalChannel, OptionalRole, Ratio, Volume},
+};
+
+use super::{
+    errors::MusicCommandError,
+    utils::{parse_removal_target, removes_only_own_songs, RemovalTarget},
+};
 
 define_setting!(music_channel: OptionalChannel);
+define_setting!(fair_queue: bool);
+define_setting!(autoplay: bool);
+define_setting!(announce_tracks: DefaultOn);
+define_setting!(vote_skip_ratio: Ratio);
+define_setting!(max_track_minutes: u64);
+define_setting!(max_user_tracks: usize);
+define_setting!(max_queue_length: usize);
+define_setting!(default_volume: Volume);
+define_setting!(dj_role: OptionalRole);
 
 pub(super) async fn get_music_channel(ctx: &Context, guild_id: GuildId) -> Option<ChannelId> {
     MUSIC_CHANNEL_SETTING.get(ctx, guild_id).await.unwrap().0
 }
 
+pub(super) async fn get_fair_queue(ctx: &Context, guild_id: GuildId) -> bool {
+    FAIR_QUEUE_SETTING.get(ctx, guild_id).await.unwrap()
+}
+
+pub(super) async fn get_autoplay(ctx: &Context, guild_id: GuildId) -> bool {
+    AUTOPLAY_SETTING.get(ctx, guild_id).await.unwrap()
+}
+
+pub(super) async fn set_autoplay(
+    ctx: &Context,
+    guild_id: GuildId,
+    value: bool,
+) -> Result<(), SettingsError> {
+    get_handler(ctx)
+        .await
+        .set_setting(ctx, guild_id, AUTOPLAY_SETTING.name(), &value.to_string())
+        .await
+}
+
+pub(super) async fn get_announce_tracks(ctx: &Context, guild_id: GuildId) -> bool {
+    ANNOUNCE_TRACKS_SETTING.get(ctx, guild_id).await.unwrap().0
+}
+
+pub(super) async fn get_vote_skip_ratio(ctx: &Context, guild_id: GuildId) -> f64 {
+    VOTE_SKIP_RATIO_SETTING.get(ctx, guild_id).await.unwrap().0
+}
+
+/// Returns the maximum duration of a track in minutes, if there is one
+pub(super) async fn get_max_track_minutes(ctx: &Context, guild_id: GuildId) -> Option<u64> {
+    let value = MAX_TRACK_MINUTES_SETTING.get(ctx, guild_id).await.unwrap();
+    (value > 0).then_some(value)
+}
+
+/// Returns the maximum amount of tracks a user can have queued, if there is one
+pub(super) async fn get_max_user_tracks(ctx: &Context, guild_id: GuildId) -> Option<usize> {
+    let value = MAX_USER_TRACKS_SETTING.get(ctx, guild_id).await.unwrap();
+    (value > 0).then_some(value)
+}
+
+/// Returns the maximum length of the queue, if there is one
+pub(super) async fn get_max_queue_length(ctx: &Context, guild_id: GuildId) -> Option<usize> {
+    let value = MAX_QUEUE_LENGTH_SETTING.get(ctx, guild_id).await.unwrap();
+    (value > 0).then_some(value)
+}
+
+pub(super) async fn get_default_volume(ctx: &Context, guild_id: GuildId) -> u32 {
+    DEFAULT_VOLUME_SETTING.get(ctx, guild_id).await.unwrap().0
+}
+
+/// Returns the role needed for the destructive music commands, if there is one
+pub(super) async fn get_dj_role(ctx: &Context, guild_id: GuildId) -> Option<RoleId> {
+    DJ_ROLE_SETTING.get(ctx, guild_id).await.unwrap().0
+}
+
+pub(super) async fn set_fair_queue(
+    ctx: &Context,
+    guild_id: GuildId,
+    value: bool,
+) -> Result<(), SettingsError> {
+    // Go through the handler so the change is saved to the settings file
+    get_handler(ctx)
+        .await
+        .set_setting(ctx, guild_id, FAIR_QUEUE_SETTING.name(), &value.to_string())
+        .await
+}
+
+/// Returns whether music commands can be used in a channel
+pub(super) async fn is_music_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> bool {
+    let music_channel = get_music_channel(ctx, guild_id).await;
+
+    music_channel.is_none() || music_channel == Some(channel_id)
+}
+
 #[check]
 pub(super) async fn in_music_channel(
     ctx: &Context,
@@ -23,11 +121,7 @@ pub(super) async fn in_music_channel(
     let guild_id = msg
         .guild_id
         .ok_or_else(|| Reason::User("No estás en un servidor".into()))?;
-    let channel_id = msg.channel_id;
-
-    let music_channel = get_music_channel(ctx, guild_id).await;
-
-    if music_channel.is_none() || music_channel == Some(channel_id) {
+    if is_music_channel(ctx, guild_id, msg.channel_id).await {
         Ok(())
     } else {
         let mention = msg.author.mention();
@@ -44,3 +138,122 @@ pub(super) async fn in_music_channel(
         Err(Reason::User("Not in music channel".into()))
     }
 }
+
+/// Checks that a user can use the destructive music commands, which
+/// everyone can while there is no DJ role
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild of the command
+/// * `user_id` - The user that runs the command
+///
+/// ## Returns
+///
+/// * `Ok(())` - The user has the DJ role or can manage the server
+/// * `Err(MusicCommandError)` - The user is not a DJ
+pub(super) async fn check_dj(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), MusicCommandError> {
+    let Some(dj_role) = get_dj_role(ctx, guild_id).await else {
+        return Ok(());
+    };
+
+    let member = guild_id
+        .member(ctx, user_id)
+        .await
+        .map_err(|_| MusicCommandError::NotDj)?;
+
+    let can_manage_guild = member
+        .permissions(ctx)
+        .is_ok_and(|permissions| permissions.manage_guild());
+
+    if member.roles.contains(&dj_role) || can_manage_guild {
+        Ok(())
+    } else {
+        Err(MusicCommandError::NotDj)
+    }
+}
+
+/// Checks that a user is a DJ if a removal would take out songs of other users
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild of the command
+/// * `user_id` - The user that runs the command
+/// * `target` - The songs to remove
+///
+/// ## Returns
+///
+/// * `Ok(())` - The user can remove the songs
+/// * `Err(MusicCommandError)` - Some songs are from other users and the user is not a DJ
+pub(super) async fn check_removal(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    target: &RemovalTarget,
+) -> Result<(), MusicCommandError> {
+    // Without a call there is nothing to remove, which the command reports itself
+    let Some(handler_lock) = songbird::get(ctx)
+        .await
+        .and_then(|manager| manager.get(guild_id))
+    else {
+        return Ok(());
+    };
+
+    if removes_only_own_songs(handler_lock, target, user_id).await {
+        Ok(())
+    } else {
+        check_dj(ctx, guild_id, user_id).await
+    }
+}
+
+/// Replies with the reason a music check failed
+async fn reject_command(ctx: &Context, msg: &Message, why: MusicCommandError) -> Reason {
+    handle_error(ctx, msg, why.to_string()).await;
+
+    Reason::User(why.to_string())
+}
+
+#[check]
+pub(super) async fn dj(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> Result<(), Reason> {
+    let guild_id = msg
+        .guild_id
+        .ok_or_else(|| Reason::User("No estás en un servidor".into()))?;
+
+    match check_dj(ctx, guild_id, msg.author.id).await {
+        Ok(()) => Ok(()),
+        Err(why) => Err(reject_command(ctx, msg, why).await),
+    }
+}
+
+// Lets anyone remove their own songs, but only DJs remove the songs of others
+#[check]
+pub(super) async fn dj_or_requester(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+    _: &CommandOptions,
+) -> Result<(), Reason> {
+    let guild_id = msg
+        .guild_id
+        .ok_or_else(|| Reason::User("No estás en un servidor".into()))?;
+
+    // Invalid arguments are reported by the command itself
+    let Ok(target) = parse_removal_target(args.rest()) else {
+        return Ok(());
+    };
+
+    match check_removal(ctx, guild_id, msg.author.id, &target).await {
+        Ok(()) => Ok(()),
+        Err(why) => Err(reject_command(ctx, msg, why).await),
+    }
+}
@@ -0,0 +1,539 @@
+// Slash commands that mirror the music commands
+
+use serde_json::Value;
+use serenity::{
+    builder::{CreateApplicationCommand, CreateApplicationCommandOption},
+    framework::standard::CommandResult,
+    model::{
+        application::{
+            command::{Command, CommandOptionType},
+            interaction::application_command::{ApplicationCommandInteraction, CommandDataOption},
+        },
+        prelude::{AttachmentId, UserId},
+    },
+    prelude::Context,
+};
+
+use crate::utils::Volume;
+
+use super::{
+    channels::{run_join, run_leave},
+    errors::MusicCommandError,
+    filters::run_filter,
+    play::{
+        run_forward, run_loop, run_pause, run_play, run_play_top, run_previous, run_rewind,
+        run_seek, run_skip, run_skipto, run_stop, run_unskip, run_volume,
+    },
+    playlists::run_playlist,
+    queue::{
+        run_autoplay, run_clear, run_dedupe, run_fair, run_history, run_insert, run_move,
+        run_now_playing, run_queue, run_remove, run_replace, run_shuffle,
+    },
+    queue_file::{run_queue_export, run_queue_import, QueueFileFormat},
+    search::run_search,
+    sessions::run_resume_session,
+    settings::{check_dj, check_removal, is_music_channel},
+    utils::{parse_removal_target, Invocation, RemovalTarget},
+};
+
+/// The commands that need the DJ role while there is one, like the prefix
+/// commands with the `dj` check
+const DJ_COMMANDS: [&str; 11] = [
+    "clear", "stop", "move", "replace", "seek", "forward", "rewind", "skipto", "unskip", "dedupe",
+    "leave",
+];
+
+/// The commands without options, with their descriptions
+const SIMPLE_COMMANDS: [(&str, &str); 15] = [
+    ("skip", "Saltea la canción actual, o vota para saltearla"),
+    (
+        "unskip",
+        "Devuelve a la cola las canciones salteadas con skipto",
+    ),
+    ("previous", "Vuelve a poner la última canción que terminó"),
+    ("pause", "Pausa la canción actual"),
+    ("stop", "Para la música y vacía la cola"),
+    (
+        "loop",
+        "Cambia el modo de loop: desactivado, canción o cola",
+    ),
+    ("now_playing", "Muestra la canción actual"),
+    ("clear", "Vacía la cola, menos la canción actual"),
+    ("dedupe", "Saca de la cola las canciones repetidas"),
+    ("shuffle", "Mezcla la cola"),
+    (
+        "fair",
+        "Activa o desactiva la cola justa, que turna a quienes piden canciones",
+    ),
+    (
+        "autoplay",
+        "Activa o desactiva la reproducción de canciones relacionadas",
+    ),
+    ("join", "Se conecta a tu canal de voz"),
+    ("leave", "Se desconecta del canal de voz"),
+    (
+        "resume_session",
+        "Restaura la cola que había cuando se apagó el bot",
+    ),
+];
+
+/// The filters that can be picked in the `filter` command
+const FILTER_CHOICES: [&str; 6] = [
+    "bassboost",
+    "nightcore",
+    "vaporwave",
+    "normalize",
+    "speed",
+    "clear",
+];
+
+/// Adds a text option to a command
+fn add_text_option<'a>(
+    command: &'a mut CreateApplicationCommand,
+    name: &str,
+    description: &str,
+    required: bool,
+) -> &'a mut CreateApplicationCommand {
+    command.create_option(|o| {
+        o.name(name)
+            .description(description)
+            .kind(CommandOptionType::String)
+            .required(required)
+    })
+}
+
+/// Adds an option for a position of the queue, which starts at 1
+fn add_index_option<'a>(
+    command: &'a mut CreateApplicationCommand,
+    name: &str,
+    description: &str,
+) -> &'a mut CreateApplicationCommand {
+    command.create_option(|o| {
+        o.name(name)
+            .description(description)
+            .kind(CommandOptionType::Integer)
+            .min_int_value(1)
+            .required(true)
+    })
+}
+
+/// Adds an option for a time, like `1:30`, `90` or `1m30s`
+fn add_time_option<'a>(
+    command: &'a mut CreateApplicationCommand,
+    description: &str,
+    required: bool,
+) -> &'a mut CreateApplicationCommand {
+    command.create_option(|o| {
+        o.name("time")
+            .description(description)
+            .kind(CommandOptionType::String)
+            .min_length(1)
+            .max_length(20)
+            .required(required)
+    })
+}
+
+/// Adds an option for the page of a list
+fn add_page_option(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command.create_option(|o| {
+        o.name("page")
+            .description("La página a mostrar")
+            .kind(CommandOptionType::Integer)
+            .min_int_value(1)
+    })
+}
+
+/// Creates a subcommand of `playlist`
+fn playlist_subcommand(
+    name: &str,
+    description: &str,
+    named: bool,
+) -> CreateApplicationCommandOption {
+    let mut subcommand = CreateApplicationCommandOption::default();
+
+    subcommand
+        .name(name)
+        .description(description)
+        .kind(CommandOptionType::SubCommand);
+
+    if named {
+        subcommand.create_sub_option(|o| {
+            o.name("name")
+                .description("El nombre de la playlist")
+                .kind(CommandOptionType::String)
+                .min_length(1)
+                .required(true)
+        });
+    }
+
+    subcommand
+}
+
+/// Registers the music slash commands, replacing the ones registered before
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+///
+/// ## Returns
+///
+/// * `Ok(Vec<Command>)` - The registered commands
+/// * `Err(serenity::Error)` - Discord rejected the commands
+pub async fn register_music_commands(ctx: &Context) -> serenity::Result<Vec<Command>> {
+    Command::set_global_application_commands(&ctx.http, |commands| {
+        for (name, description) in SIMPLE_COMMANDS {
+            commands.create_application_command(|c| {
+                c.name(name).description(description).dm_permission(false)
+            });
+        }
+
+        commands
+            .create_application_command(|c| {
+                c.name("play")
+                    .description("Agrega una canción o playlist a la cola, o reanuda la música")
+                    .dm_permission(false);
+                add_text_option(c, "query", "Una búsqueda o un link", false)
+            })
+            .create_application_command(|c| {
+                c.name("play_top")
+                    .description("Agrega una canción o playlist después de la actual")
+                    .dm_permission(false);
+                add_text_option(c, "query", "Una búsqueda o un link", true)
+            })
+            .create_application_command(|c| {
+                c.name("search")
+                    .description("Busca canciones y te deja elegir cuál agregar")
+                    .dm_permission(false);
+                add_text_option(c, "query", "La búsqueda", true)
+            })
+            .create_application_command(|c| {
+                c.name("skipto")
+                    .description("Saltea las canciones hasta una posición de la cola")
+                    .dm_permission(false);
+                add_index_option(c, "index", "La posición de la canción a poner")
+            })
+            .create_application_command(|c| {
+                c.name("seek")
+                    .description("Salta a un momento de la canción actual")
+                    .dm_permission(false);
+                add_time_option(c, "Un tiempo (1:30, 90, 1m30s) o un salto (+30, -10)", true)
+            })
+            .create_application_command(|c| {
+                c.name("forward")
+                    .description("Adelanta la canción actual")
+                    .dm_permission(false);
+                add_time_option(c, "Cuánto adelantar, 10 segundos si no se indica", false)
+            })
+            .create_application_command(|c| {
+                c.name("rewind")
+                    .description("Retrocede la canción actual")
+                    .dm_permission(false);
+                add_time_option(c, "Cuánto retroceder, 10 segundos si no se indica", false)
+            })
+            .create_application_command(|c| {
+                c.name("volume")
+                    .description("Cambia el volumen, o lo muestra si no se indica")
+                    .dm_permission(false)
+                    .create_option(|o| {
+                        o.name("volume")
+                            .description("El volumen en porcentaje")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(0)
+                            .max_int_value(Volume::MAX)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("filter")
+                    .description("Activa o desactiva un filtro, o muestra los activos")
+                    .dm_permission(false)
+                    .create_option(|o| {
+                        o.name("name")
+                            .description("El filtro")
+                            .kind(CommandOptionType::String);
+
+                        for filter in FILTER_CHOICES {
+                            o.add_string_choice(filter, filter);
+                        }
+
+                        o
+                    })
+                    .create_option(|o| {
+                        o.name("speed")
+                            .description("La velocidad del filtro speed")
+                            .kind(CommandOptionType::Number)
+                            .min_number_value(0.5)
+                            .max_number_value(2.0)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("queue")
+                    .description("Muestra la cola")
+                    .dm_permission(false);
+                add_page_option(c)
+            })
+            .create_application_command(|c| {
+                c.name("queue_export")
+                    .description("Sube la cola como un archivo")
+                    .dm_permission(false)
+                    .create_option(|o| {
+                        o.name("format")
+                            .description("El formato del archivo")
+                            .kind(CommandOptionType::String)
+                            .add_string_choice("Links", "txt")
+                            .add_string_choice("M3U", "m3u")
+                            .add_string_choice("RON", "ron")
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("queue_import")
+                    .description("Agrega a la cola las canciones de un archivo")
+                    .dm_permission(false)
+                    .create_option(|o| {
+                        o.name("file")
+                            .description("Un archivo exportado con /queue_export")
+                            .kind(CommandOptionType::Attachment)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("history")
+                    .description("Muestra las canciones que ya terminaron")
+                    .dm_permission(false);
+                add_page_option(c)
+            })
+            .create_application_command(|c| {
+                c.name("insert")
+                    .description("Agrega una canción o playlist en una posición de la cola")
+                    .dm_permission(false);
+                add_index_option(c, "index", "La posición en la cola");
+                add_text_option(c, "query", "Una búsqueda o un link", true)
+            })
+            .create_application_command(|c| {
+                c.name("move")
+                    .description("Mueve una canción a otra posición de la cola")
+                    .dm_permission(false);
+                add_index_option(c, "from", "La posición de la canción");
+                add_index_option(c, "to", "La nueva posición de la canción")
+            })
+            .create_application_command(|c| {
+                c.name("remove")
+                    .description("Saca canciones de la cola por posición o por quien las pidió")
+                    .dm_permission(false)
+                    .create_option(|o| {
+                        o.name("positions")
+                            .description("Posiciones y rangos, como 3-7 o 2 5 9")
+                            .kind(CommandOptionType::String)
+                            .min_length(1)
+                    })
+                    .create_option(|o| {
+                        o.name("user")
+                            .description("Saca todas las canciones que pidió")
+                            .kind(CommandOptionType::User)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("replace")
+                    .description("Reemplaza la última canción de la cola")
+                    .dm_permission(false);
+                add_text_option(c, "query", "Una búsqueda o un link", true)
+            })
+            .create_application_command(|c| {
+                c.name("playlist")
+                    .description("Administra las playlists guardadas")
+                    .dm_permission(false)
+                    .add_option(playlist_subcommand(
+                        "save",
+                        "Guarda la cola como una playlist",
+                        true,
+                    ))
+                    .add_option(playlist_subcommand(
+                        "load",
+                        "Agrega una playlist a la cola",
+                        true,
+                    ))
+                    .add_option(playlist_subcommand(
+                        "list",
+                        "Muestra las playlists guardadas",
+                        false,
+                    ))
+                    .add_option(playlist_subcommand(
+                        "show",
+                        "Muestra las canciones de una playlist",
+                        true,
+                    ))
+                    .add_option(playlist_subcommand("delete", "Elimina una playlist", true))
+            })
+    })
+    .await
+}
+
+/// Returns the value of an option of a slash command
+fn option_value<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a Value> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+}
+
+/// Returns the value of a text option, or an empty string if it was not given
+fn text_value<'a>(options: &'a [CommandDataOption], name: &str) -> &'a str {
+    option_value(options, name)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+/// Returns the value of a queue position option
+fn index_value(options: &[CommandDataOption], name: &str) -> Result<usize, MusicCommandError> {
+    option_value(options, name)
+        .and_then(Value::as_u64)
+        .and_then(|index| usize::try_from(index).ok())
+        .filter(|&index| index > 0)
+        .ok_or(MusicCommandError::InvalidQueueIndex)
+}
+
+/// Returns the value of a page option, or the first page if it was not given
+fn page_value(options: &[CommandDataOption]) -> usize {
+    option_value(options, "page")
+        .and_then(Value::as_u64)
+        .and_then(|page| usize::try_from(page).ok())
+        .unwrap_or(1)
+}
+
+/// Runs a slash command through the same code as its prefix command
+async fn run_music_command(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+    interaction: &ApplicationCommandInteraction,
+) -> CommandResult {
+    if !is_music_channel(ctx, invocation.guild_id(), invocation.channel_id()).await {
+        return Err(MusicCommandError::NotInMusicChannel.into());
+    }
+
+    if DJ_COMMANDS.contains(&interaction.data.name.as_str()) {
+        check_dj(ctx, invocation.guild_id(), invocation.author().id).await?;
+    }
+
+    let options = interaction.data.options.as_slice();
+
+    match interaction.data.name.as_str() {
+        "play" => run_play(ctx, invocation, text_value(options, "query")).await,
+        "play_top" => run_play_top(ctx, invocation, text_value(options, "query")).await,
+        "search" => run_search(ctx, invocation, text_value(options, "query")).await,
+        "skip" => run_skip(ctx, invocation).await,
+        "skipto" => run_skipto(ctx, invocation, index_value(options, "index")?).await,
+        "unskip" => run_unskip(ctx, invocation).await,
+        "previous" => run_previous(ctx, invocation).await,
+        "pause" => run_pause(ctx, invocation).await,
+        "stop" => run_stop(ctx, invocation).await,
+        "seek" => run_seek(ctx, invocation, text_value(options, "time")).await,
+        "forward" => run_forward(ctx, invocation, text_value(options, "time")).await,
+        "rewind" => run_rewind(ctx, invocation, text_value(options, "time")).await,
+        "loop" => run_loop(ctx, invocation).await,
+        "volume" => {
+            let volume = option_value(options, "volume")
+                .map(|volume| volume.to_string().parse::<Volume>())
+                .transpose()
+                .map_err(|_| MusicCommandError::InvalidVolume)?;
+
+            run_volume(ctx, invocation, volume).await
+        }
+        "filter" => {
+            let name = option_value(options, "name").and_then(Value::as_str);
+            let speed = option_value(options, "speed").map(Value::to_string);
+
+            run_filter(ctx, invocation, name, speed.as_deref()).await
+        }
+        "queue" => run_queue(ctx, invocation, page_value(options)).await,
+        "queue_export" => {
+            let format = text_value(options, "format").parse::<QueueFileFormat>()?;
+
+            run_queue_export(ctx, invocation, format).await
+        }
+        "queue_import" => {
+            let attachment = option_value(options, "file")
+                .and_then(Value::as_str)
+                .and_then(|attachment_id| attachment_id.parse::<u64>().ok())
+                .and_then(|attachment_id| {
+                    interaction
+                        .data
+                        .resolved
+                        .attachments
+                        .get(&AttachmentId(attachment_id))
+                });
+
+            run_queue_import(ctx, invocation, attachment).await
+        }
+        "history" => run_history(ctx, invocation, page_value(options)).await,
+        "now_playing" => run_now_playing(ctx, invocation).await,
+        "insert" => {
+            let index = index_value(options, "index")?;
+
+            run_insert(ctx, invocation, index, text_value(options, "query")).await
+        }
+        "move" => {
+            let from = index_value(options, "from")?;
+            let to = index_value(options, "to")?;
+
+            run_move(ctx, invocation, from, to).await
+        }
+        "remove" => {
+            let requester = option_value(options, "user")
+                .and_then(Value::as_str)
+                .and_then(|user_id| user_id.parse::<u64>().ok());
+
+            let target = match requester {
+                Some(user_id) => RemovalTarget::Requester(UserId(user_id)),
+                None => parse_removal_target(text_value(options, "positions"))?,
+            };
+
+            check_removal(ctx, invocation.guild_id(), invocation.author().id, &target).await?;
+
+            run_remove(ctx, invocation, target).await
+        }
+        "dedupe" => run_dedupe(ctx, invocation).await,
+        "replace" => run_replace(ctx, invocation, text_value(options, "query")).await,
+        "clear" => run_clear(ctx, invocation).await,
+        "shuffle" => run_shuffle(ctx, invocation).await,
+        "fair" => run_fair(ctx, invocation).await,
+        "autoplay" => run_autoplay(ctx, invocation).await,
+        "playlist" => {
+            let subcommand = options.first().ok_or(MusicCommandError::Generic)?;
+            let name = text_value(&subcommand.options, "name");
+
+            run_playlist(ctx, invocation, &subcommand.name, name).await
+        }
+        "join" => run_join(ctx, invocation).await,
+        "leave" => run_leave(ctx, invocation).await,
+        "resume_session" => run_resume_session(ctx, invocation).await,
+        _ => Err(MusicCommandError::Generic.into()),
+    }
+}
+
+/// Handles a music slash command. Its errors are only shown to the user that ran it
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `interaction` - The slash command
+pub async fn handle_music_command(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+    if interaction.guild_id.is_none() {
+        return;
+    }
+
+    let name = &interaction.data.name;
+    println!("Running /{name} command");
+
+    // Searches take longer than the time Discord waits for an answer
+    if let Err(why) = interaction.defer(&ctx.http).await {
+        println!("Could not defer /{name}: {why:?}");
+        return;
+    }
+
+    let invocation = Invocation::from(interaction);
+
+    let result = run_music_command(ctx, &invocation, interaction).await;
+
+    invocation.finish(ctx, result).await;
+
+    println!("Finished running /{name} command");
+}
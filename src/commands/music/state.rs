@@ -0,0 +1,155 @@
+// Per-guild player state
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+};
+
+use serenity::{
+    model::prelude::{ChannelId, GuildId, MessageId, UserId},
+    prelude::{Context, TypeMapKey},
+};
+use songbird::tracks::TrackHandle;
+
+use super::filters::AudioFilter;
+
+/// The maximum amount of finished tracks remembered for each guild
+const HISTORY_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) enum LoopMode {
+    #[default]
+    Off,
+    Track,
+    Queue,
+}
+
+impl LoopMode {
+    /// Returns the mode that follows this one when cycling with the `loop` command
+    pub(super) fn next(self) -> Self {
+        match self {
+            LoopMode::Off => LoopMode::Track,
+            LoopMode::Track => LoopMode::Queue,
+            LoopMode::Queue => LoopMode::Off,
+        }
+    }
+}
+
+impl Display for LoopMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoopMode::Off => write!(f, "➡️ Desactivado"),
+            LoopMode::Track => write!(f, "🔂 Canción"),
+            LoopMode::Queue => write!(f, "🔁 Cola"),
+        }
+    }
+}
+
+/// The state of the player of a guild that is not stored in songbird
+#[derive(Debug, Clone, Default)]
+pub(super) struct MusicState {
+    pub(super) loop_mode: LoopMode,
+    /// The finished tracks, from oldest to newest
+    pub(super) history: VecDeque<TrackHandle>,
+    /// The volume set with the `volume` command, which overrides `default_volume`
+    pub(super) volume: Option<u32>,
+    /// The ffmpeg filters applied to every track
+    pub(super) filters: Vec<AudioFilter>,
+    /// The last message that announced a track, so it can be replaced by the next one
+    pub(super) announcement: Option<(ChannelId, MessageId)>,
+    /// The `now_playing` message that is being kept up to date
+    pub(super) live_message: Option<(ChannelId, MessageId)>,
+    /// The songs skipped by the last `skipto`, starting with the one that was
+    /// playing, so `unskip` can bring them back
+    pub(super) skipped: Vec<TrackHandle>,
+    /// The track being voted to skip
+    skip_vote_track: Option<TrackHandle>,
+    /// The users that voted to skip `skip_vote_track`
+    skip_votes: HashSet<UserId>,
+}
+
+impl MusicState {
+    /// Adds a finished track to the history, forgetting the oldest one if it's full
+    pub(super) fn push_history(&mut self, track: TrackHandle) {
+        if self.history.len() >= HISTORY_SIZE {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(track);
+    }
+
+    /// Adds a vote to skip a track, discarding the votes for any other track
+    ///
+    /// ## Arguments
+    ///
+    /// * `track` - The track being voted to skip
+    /// * `user` - The user that voted
+    ///
+    /// ## Returns
+    ///
+    /// * `usize` - The amount of votes to skip the track
+    pub(super) fn add_skip_vote(&mut self, track: &TrackHandle, user: UserId) -> usize {
+        let same_track = self
+            .skip_vote_track
+            .as_ref()
+            .is_some_and(|voted| voted.uuid() == track.uuid());
+
+        if !same_track {
+            self.skip_vote_track = Some(track.clone());
+            self.skip_votes.clear();
+        }
+
+        self.skip_votes.insert(user);
+        self.skip_votes.len()
+    }
+}
+
+pub(super) struct MusicStates;
+
+impl TypeMapKey for MusicStates {
+    type Value = HashMap<GuildId, MusicState>;
+}
+
+/// Returns a copy of the player state of a guild
+///
+/// ## Arguments
+///
+/// * `ctx` - The context to get the state from
+/// * `guild_id` - The guild to get the state of
+///
+/// ## Returns
+///
+/// * `MusicState` - The state of the guild, or the default one if it was never set
+pub(super) async fn get_music_state(ctx: &Context, guild_id: GuildId) -> MusicState {
+    let data = ctx.data.read().await;
+
+    data.get::<MusicStates>()
+        .and_then(|states| states.get(&guild_id).cloned())
+        .unwrap_or_default()
+}
+
+/// Modifies the player state of a guild
+///
+/// ## Arguments
+///
+/// * `ctx` - The context to get the state from
+/// * `guild_id` - The guild to modify the state of
+/// * `func` - The function that modifies the state
+///
+/// ## Returns
+///
+/// * `O` - The value returned by `func`
+pub(super) async fn update_music_state<F, O>(ctx: &Context, guild_id: GuildId, func: F) -> O
+where
+    F: FnOnce(&mut MusicState) -> O,
+{
+    let mut data = ctx.data.write().await;
+
+    let state = data
+        .entry::<MusicStates>()
+        .or_default()
+        .entry(guild_id)
+        .or_default();
+
+    func(state)
+}
@@ -1,32 +1,51 @@
 // Shared utility functions for the music commands
 
 use std::{
+    collections::{BTreeSet, HashMap, HashSet},
     env,
+    ops::RangeInclusive,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     time::Duration,
 };
 
+use itertools::Itertools;
 use lazy_static::lazy_static;
+use rand::seq::SliceRandom;
 use regex::Regex;
+use serde::Deserialize;
 use serenity::{
     async_trait,
+    builder::{CreateComponents, CreateEmbed},
+    framework::standard::CommandResult,
     model::{
-        channel::Message,
-        prelude::{ChannelId, UserId},
+        application::interaction::application_command::ApplicationCommandInteraction,
+        channel::{AttachmentType, Message},
+        prelude::{ChannelId, GuildId, User, UserId},
     },
     prelude::{Context, Mutex, TypeMapKey},
+    utils::parse_username,
 };
 use songbird::{
-    id::GuildId,
-    input::{Input, Restartable},
-    tracks::PlayMode,
-    Call, Event, EventContext, EventHandler, Songbird,
+    input::{Input, Metadata, Restartable},
+    tracks::{PlayMode, TrackHandle, TrackQueue},
+    Call, Event, EventContext, EventHandler, Songbird, TrackEvent,
 };
 
-use super::errors::MusicCommandError;
+use super::{
+    cache::cache_song,
+    errors::{DurationError, MusicCommandError},
+    events::{TrackEndHandler, TrackStartHandler},
+    filters::{filtered_source, lazy_source, output_time, source_time, AudioFilter},
+    sessions::SessionHandler,
+    settings::{
+        get_autoplay, get_default_volume, get_fair_queue, get_max_queue_length,
+        get_max_track_minutes, get_max_user_tracks, get_vote_skip_ratio,
+    },
+    state::{get_music_state, update_music_state, LoopMode},
+};
 
 lazy_static! {
     /// The time in seconds between each check for idle voice
@@ -43,9 +62,23 @@ lazy_static! {
         .parse()
         .unwrap();
     static ref IDLE_MAX_COUNTS: u64 = *IDLE_MAX_TIME / *IDLE_CHECK_PERIOD;
+
+    /// The time in seconds between each snapshot of the queue
+    /// saved to the sessions file
+    static ref SESSION_SAVE_PERIOD: u64 = env::var("SESSION_SAVE_PERIOD")
+        .unwrap_or_else(|_| "15".to_string())
+        .parse()
+        .unwrap();
+
+    /// The maximum amount of songs added from a single playlist
+    static ref PLAYLIST_MAX_ITEMS: usize = env::var("PLAYLIST_MAX_ITEMS")
+        .unwrap_or_else(|_| "50".to_string())
+        .parse()
+        .unwrap();
 }
 
 struct IdleHandler {
+    ctx: Context,
     manager: Arc<Songbird>,
     guild_id: GuildId,
     count: Arc<AtomicU64>,
@@ -68,6 +101,24 @@ impl EventHandler for IdleHandler {
             let count = self.count.fetch_add(1, Ordering::Relaxed);
 
             if count >= *IDLE_MAX_COUNTS {
+                // Autoplay keeps the bot around while someone is listening
+                if get_autoplay(&self.ctx, self.guild_id).await {
+                    let channel_id = {
+                        let handler_lock = self.manager.get(self.guild_id)?;
+                        let handler = handler_lock.lock().await;
+                        handler.current_channel()
+                    };
+
+                    let listeners = channel_id.map_or(0, |channel_id| {
+                        count_listeners(&self.ctx, self.guild_id, ChannelId(channel_id.0))
+                    });
+
+                    if listeners > 0 {
+                        self.count.store(0, Ordering::Relaxed);
+                        return None;
+                    }
+                }
+
                 self.manager.remove(self.guild_id).await.ok()?;
             }
         }
@@ -76,14 +127,256 @@ impl EventHandler for IdleHandler {
     }
 }
 
-/// Return a lock to the songbird handler, optionally joining the channel
-/// if the bot is not in it
+/// Where a music command was run from
+#[derive(Clone, Copy)]
+enum InvocationSource<'a> {
+    Message(&'a Message),
+    Interaction(&'a ApplicationCommandInteraction),
+}
+
+/// A music command being run, either from a prefix message or from a slash
+/// command, so both run through the same code
+pub(super) struct Invocation<'a> {
+    source: InvocationSource<'a>,
+    /// Whether the deferred response of the interaction was already sent
+    responded: AtomicBool,
+}
+
+impl<'a> From<&'a Message> for Invocation<'a> {
+    fn from(msg: &'a Message) -> Self {
+        Self {
+            source: InvocationSource::Message(msg),
+            responded: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<'a> From<&'a ApplicationCommandInteraction> for Invocation<'a> {
+    fn from(interaction: &'a ApplicationCommandInteraction) -> Self {
+        Self {
+            source: InvocationSource::Interaction(interaction),
+            responded: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Invocation<'_> {
+    /// The guild the command was run in. Music commands only run in guilds
+    pub(super) fn guild_id(&self) -> GuildId {
+        match self.source {
+            InvocationSource::Message(msg) => msg.guild_id.unwrap(),
+            InvocationSource::Interaction(interaction) => interaction.guild_id.unwrap(),
+        }
+    }
+
+    /// The user that ran the command
+    pub(super) fn author(&self) -> &User {
+        match self.source {
+            InvocationSource::Message(msg) => &msg.author,
+            InvocationSource::Interaction(interaction) => &interaction.user,
+        }
+    }
+
+    /// The text channel the command was run in
+    pub(super) fn channel_id(&self) -> ChannelId {
+        match self.source {
+            InvocationSource::Message(msg) => msg.channel_id,
+            InvocationSource::Interaction(interaction) => interaction.channel_id,
+        }
+    }
+
+    /// Sends a message to the channel of the command. Slash commands are
+    /// answered by replacing their deferred response first, then with followups
+    ///
+    /// ## Arguments
+    ///
+    /// * `ctx` - The context of the bot
+    /// * `content` - The text of the message
+    /// * `embed` - The embed of the message
+    /// * `components` - The components of the message
+    ///
+    /// ## Returns
+    ///
+    /// * `Ok(Message)` - The message that was sent
+    /// * `Err(serenity::Error)` - The message could not be sent
+    async fn send(
+        &self,
+        ctx: &Context,
+        content: Option<String>,
+        embed: Option<CreateEmbed>,
+        components: CreateComponents,
+    ) -> serenity::Result<Message> {
+        let InvocationSource::Interaction(interaction) = self.source else {
+            return self
+                .channel_id()
+                .send_message(&ctx.http, |m| {
+                    if let Some(content) = content {
+                        m.content(content);
+                    }
+                    if let Some(embed) = embed {
+                        m.set_embed(embed);
+                    }
+                    m.set_components(components)
+                })
+                .await;
+        };
+
+        if self.responded.swap(true, Ordering::Relaxed) {
+            interaction
+                .create_followup_message(&ctx.http, |m| {
+                    if let Some(content) = content {
+                        m.content(content);
+                    }
+                    if let Some(embed) = embed {
+                        m.set_embed(embed);
+                    }
+                    m.set_components(components)
+                })
+                .await
+        } else {
+            interaction
+                .edit_original_interaction_response(&ctx.http, |m| {
+                    if let Some(content) = content {
+                        m.content(content);
+                    }
+                    if let Some(embed) = embed {
+                        m.set_embed(embed);
+                    }
+                    m.components(|c| {
+                        *c = components;
+                        c
+                    })
+                })
+                .await
+        }
+    }
+
+    /// Sends a text message to the channel of the command
+    pub(super) async fn say(
+        &self,
+        ctx: &Context,
+        content: impl ToString,
+    ) -> serenity::Result<Message> {
+        self.send(
+            ctx,
+            Some(content.to_string()),
+            None,
+            CreateComponents::default(),
+        )
+        .await
+    }
+
+    /// Replies to the command. Prefix commands quote the message that ran them
+    pub(super) async fn reply(
+        &self,
+        ctx: &Context,
+        content: impl ToString,
+    ) -> serenity::Result<Message> {
+        match self.source {
+            InvocationSource::Message(msg) => msg.reply(ctx, content.to_string()).await,
+            InvocationSource::Interaction(_) => self.say(ctx, content).await,
+        }
+    }
+
+    /// Sends a message with a file to the channel of the command
+    ///
+    /// ## Arguments
+    ///
+    /// * `ctx` - The context of the bot
+    /// * `content` - The text of the message
+    /// * `data` - The contents of the file
+    /// * `filename` - The name of the file
+    ///
+    /// ## Returns
+    ///
+    /// * `Ok(Message)` - The message with the file
+    /// * `Err(serenity::Error)` - The message could not be sent
+    pub(super) async fn send_file(
+        &self,
+        ctx: &Context,
+        content: impl ToString,
+        data: Vec<u8>,
+        filename: String,
+    ) -> serenity::Result<Message> {
+        let file = AttachmentType::Bytes {
+            data: data.into(),
+            filename,
+        };
+
+        match self.source {
+            InvocationSource::Message(msg) => {
+                msg.channel_id
+                    .send_message(&ctx.http, |m| m.content(content.to_string()).add_file(file))
+                    .await
+            }
+            // The original response of an interaction can't get files when edited,
+            // so they go in a followup
+            InvocationSource::Interaction(interaction) => {
+                self.say(ctx, content).await?;
+
+                interaction
+                    .create_followup_message(&ctx.http, |m| m.add_file(file))
+                    .await
+            }
+        }
+    }
+
+    /// Sends an embed with components to the channel of the command
+    pub(super) async fn send_embed<F>(
+        &self,
+        ctx: &Context,
+        embed: CreateEmbed,
+        components: F,
+    ) -> serenity::Result<Message>
+    where
+        F: FnOnce(&mut CreateComponents) -> &mut CreateComponents,
+    {
+        let mut created = CreateComponents::default();
+        components(&mut created);
+
+        self.send(ctx, None, Some(embed), created).await
+    }
+
+    /// Ends a slash command. The deferred response is removed if the command
+    /// didn't answer, and errors are only shown to the user that ran it.
+    /// Prefix commands are left to the `after` hook of the framework
+    ///
+    /// ## Arguments
+    ///
+    /// * `ctx` - The context of the bot
+    /// * `result` - The result of the command
+    pub(super) async fn finish(&self, ctx: &Context, result: CommandResult) {
+        let InvocationSource::Interaction(interaction) = self.source else {
+            return;
+        };
+
+        if !self.responded.load(Ordering::Relaxed) {
+            interaction
+                .delete_original_interaction_response(&ctx.http)
+                .await
+                .ok();
+        }
+
+        if let Err(why) = result {
+            println!("Error running slash command: {why:?}");
+
+            interaction
+                .create_followup_message(&ctx.http, |m| {
+                    m.content(format!("⚠️ **Error**: {why}")).ephemeral(true)
+                })
+                .await
+                .ok();
+        }
+    }
+}
+
+/// Return a lock to the songbird handler, joining the voice channel of the
+/// user that ran the command.
 ///
 /// ## Arguments
 ///
-/// * `ctx` - The context of the message
-/// * `msg` - The message to get the guild and channel from
-/// * `join_if_not_in_channel` - Whether to join the channel if the bot is not in it
+/// * `ctx` - The context of the bot
+/// * `invocation` - The command to get the guild and user from
 ///
 /// ## Returns
 ///
@@ -91,19 +384,58 @@ impl EventHandler for IdleHandler {
 /// * `Err(&str)` - The bot failed to join the voice channel
 pub(super) async fn get_handler_lock(
     ctx: &Context,
-    msg: &Message,
+    invocation: &Invocation<'_>,
 ) -> Result<Arc<Mutex<Call>>, MusicCommandError> {
-    let manager = songbird::get(ctx).await.unwrap().clone();
+    let guild_id = invocation.guild_id();
+    let channel_id = get_voice_channel(ctx, invocation)?;
+
+    join_voice_channel(ctx, guild_id, channel_id).await
+}
 
-    let guild = msg.guild(&ctx.cache).unwrap();
+/// Return the voice channel of the user that ran the command
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `invocation` - The command to get the guild and user from
+///
+/// ## Returns
+///
+/// * `Ok(ChannelId)` - The voice channel of the user
+/// * `Err(MusicCommandError)` - The user is not in a voice channel
+pub(super) fn get_voice_channel(
+    ctx: &Context,
+    invocation: &Invocation<'_>,
+) -> Result<ChannelId, MusicCommandError> {
+    let guild = ctx.cache.guild(invocation.guild_id()).unwrap();
 
-    let channel_id = guild
+    guild
         .voice_states
-        .get(&msg.author.id)
+        .get(&invocation.author().id)
         .and_then(|vs| vs.channel_id)
-        .ok_or(MusicCommandError::NoVoiceChannel)?;
+        .ok_or(MusicCommandError::NoVoiceChannel)
+}
+
+/// Joins a voice channel and sets up the events of the call
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild of the voice channel
+/// * `channel_id` - The voice channel to join
+///
+/// ## Returns
+///
+/// * `Ok(Arc<Mutex<Call>>)` - The lock to the songbird handler
+/// * `Err(MusicCommandError)` - The bot failed to join the voice channel
+pub(super) async fn join_voice_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<Arc<Mutex<Call>>, MusicCommandError> {
+    let manager = songbird::get(ctx).await.unwrap().clone();
 
-    let (handler_lock, success) = manager.join(guild.id, channel_id).await;
+    let (handler_lock, success) = manager.join(guild_id, channel_id).await;
 
     if success.is_err() {
         return Err(MusicCommandError::FailedToJoinChannel);
@@ -117,11 +449,32 @@ pub(super) async fn get_handler_lock(
         handler.add_global_event(
             Event::Periodic(Duration::from_secs(*IDLE_CHECK_PERIOD), None),
             IdleHandler {
+                ctx: ctx.clone(),
                 manager: manager.clone(),
-                guild_id: guild.id.into(),
+                guild_id,
                 count: Arc::new(AtomicU64::new(0)),
             },
         );
+
+        handler.add_global_event(
+            Event::Track(TrackEvent::End),
+            TrackEndHandler {
+                ctx: ctx.clone(),
+                guild_id,
+            },
+        );
+
+        let queue = handler.queue().clone();
+
+        handler.add_global_event(
+            Event::Periodic(Duration::from_secs(*SESSION_SAVE_PERIOD), None),
+            SessionHandler {
+                ctx: ctx.clone(),
+                guild_id,
+                voice_channel: channel_id,
+                queue,
+            },
+        );
     }
 
     Ok(handler_lock)
@@ -181,6 +534,152 @@ pub(super) async fn resume_song(handler_lock: Arc<Mutex<Call>>) -> Result<(), Mu
     Ok(())
 }
 
+/// The result of asking to skip the current song
+pub(super) enum SkipOutcome {
+    Skipped(TrackHandle),
+    Voted { votes: usize, required: usize },
+}
+
+/// Skips the current song if the user requested it or can manage the guild.
+/// Anyone else adds a vote, and the song is skipped once the share of listeners
+/// in `vote_skip_ratio` voted.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `user_id` - The user that wants to skip
+/// * `handler_lock` - The lock to the songbird handler
+///
+/// ## Returns
+///
+/// * `Ok(SkipOutcome)` - Whether the song was skipped or the vote was counted
+/// * `Err(MusicCommandError)` - No song was playing
+pub(super) async fn skip_or_vote(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    handler_lock: Arc<Mutex<Call>>,
+) -> Result<SkipOutcome, MusicCommandError> {
+    let handler = handler_lock.lock().await;
+
+    let queue = handler.queue();
+
+    let track = queue.current().ok_or(MusicCommandError::NoSongPlaying)?;
+
+    let is_requester = get_requester(&track).await == Some(user_id);
+
+    let is_manager = match guild_id.to_guild_cached(&ctx.cache) {
+        Some(guild) => guild
+            .member_permissions(ctx, user_id)
+            .await
+            .is_ok_and(|permissions| permissions.manage_guild()),
+        None => false,
+    };
+
+    // Anyone else has to reach the share of listeners in `vote_skip_ratio`
+    if !is_requester && !is_manager {
+        let channel_id = ChannelId(handler.current_channel().unwrap().0);
+        let listeners = count_listeners(ctx, guild_id, channel_id);
+
+        let ratio = get_vote_skip_ratio(ctx, guild_id).await;
+        let required = ((listeners as f64 * ratio).ceil() as usize).max(1);
+
+        let votes =
+            update_music_state(ctx, guild_id, |state| state.add_skip_vote(&track, user_id)).await;
+
+        if votes < required {
+            return Ok(SkipOutcome::Voted { votes, required });
+        }
+    }
+
+    queue.skip().map_err(|_| MusicCommandError::Generic)?;
+
+    Ok(SkipOutcome::Skipped(track))
+}
+
+/// Drops the songs between the current one and a position of the queue, then
+/// plays the song at that position
+///
+/// ## Arguments
+///
+/// * `handler_lock` - The lock to the songbird handler
+/// * `index` - The position of the song to play
+///
+/// ## Returns
+///
+/// * `Ok((TrackHandle, Vec<TrackHandle>))` - The song that will play, and the skipped
+///   songs starting with the one that was playing
+/// * `Err(MusicCommandError)` - The position is not in the queue
+pub(super) async fn skip_to(
+    handler_lock: Arc<Mutex<Call>>,
+    index: usize,
+) -> Result<(TrackHandle, Vec<TrackHandle>), MusicCommandError> {
+    let handler = handler_lock.lock().await;
+
+    let queue = handler.queue();
+
+    if index >= queue.len() || index == 0 {
+        return Err(MusicCommandError::InvalidQueueIndex);
+    }
+
+    let (target, skipped) = queue.modify_queue(|q| {
+        let skipped = q
+            .range(..index)
+            .map(|queued| queued.handle())
+            .collect::<Vec<_>>();
+
+        q.drain(1..index);
+
+        (q[1].handle(), skipped)
+    });
+
+    // The dropped songs keep their metadata in their handles, but they
+    // have to be stopped to free their sources
+    for track in &skipped[1..] {
+        track.stop().ok();
+    }
+
+    queue.skip().map_err(|_| MusicCommandError::Generic)?;
+
+    Ok((target, skipped))
+}
+
+/// Switches to the next loop mode and applies it to the current song
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `handler_lock` - The lock to the songbird handler
+///
+/// ## Returns
+///
+/// * `LoopMode` - The new loop mode
+pub(super) async fn cycle_loop_mode(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+) -> LoopMode {
+    let loop_mode = update_music_state(ctx, guild_id, |state| {
+        state.loop_mode = state.loop_mode.next();
+        state.loop_mode
+    })
+    .await;
+
+    let handler = handler_lock.lock().await;
+
+    if let Some(track) = handler.queue().current() {
+        if loop_mode == LoopMode::Track {
+            track.enable_loop().ok();
+        } else {
+            track.disable_loop().ok();
+        }
+    }
+
+    loop_mode
+}
+
 /// Stops the player
 ///
 /// ## Arguments
@@ -205,12 +704,62 @@ pub(super) async fn stop_player(handler_lock: Arc<Mutex<Call>>) -> Result<(), Mu
     Ok(())
 }
 
-lazy_static! {
-    static ref DURATION_REGEX: Regex =
-        Regex::new(r"^(?:(?:([01]?\d|2[0-3]):)?([0-5]?\d):)?([0-5]?\d)$").unwrap();
+/// Counts the users listening in a voice channel, ignoring bots
+///
+/// ## Arguments
+///
+/// * `ctx` - The context to get the guild from
+/// * `guild_id` - The guild of the voice channel
+/// * `channel_id` - The voice channel
+///
+/// ## Returns
+///
+/// * `usize` - The amount of non-bot members in the channel
+pub(super) fn count_listeners(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> usize {
+    let Some(guild) = guild_id.to_guild_cached(&ctx.cache) else {
+        return 0;
+    };
+
+    guild
+        .voice_states
+        .values()
+        .filter(|vs| vs.channel_id == Some(channel_id))
+        .filter(|vs| {
+            let is_bot = vs
+                .member
+                .as_ref()
+                .or_else(|| guild.members.get(&vs.user_id))
+                .is_some_and(|member| member.user.bot);
+
+            !is_bot
+        })
+        .count()
 }
 
-/// Parses a string in the format `hh:mm:ss` or `sss` to a `Duration`
+/// Returns the user that requested a track
+///
+/// ## Arguments
+///
+/// * `track` - The track to get the requester of
+///
+/// ## Returns
+///
+/// * `Some(UserId)` - The user that requested the track
+/// * `None` - The track was not added with `insert_song`
+pub(super) async fn get_requester(track: &TrackHandle) -> Option<UserId> {
+    track
+        .typemap()
+        .read()
+        .await
+        .get::<TrackRequester>()
+        .copied()
+}
+
+/// Parses a duration, which can be written as
+///
+/// * seconds: `90`
+/// * `hh:mm:ss` or `mm:ss`: `1:30:00`, `1:30` (the first part has no upper limit)
+/// * units: `1h2m3s`, `2m`, `90s`, `1m30` (a number without a unit is in seconds)
 ///
 /// ## Arguments
 ///
@@ -218,34 +767,180 @@ lazy_static! {
 ///
 /// ## Returns
 ///
-/// * `Some(Duration)` - The parsed duration
-/// * `None` - The string was not in the correct format
-pub(super) fn parse_duration(input: &str) -> Option<Duration> {
-    // If the input is a number, it's a duration in seconds
-    if let Ok(seconds) = input.parse::<u64>() {
-        return Some(Duration::from_secs(seconds));
+/// * `Ok(Duration)` - The parsed duration
+/// * `Err(DurationError)` - What was wrong with the string
+pub(super) fn parse_duration(input: &str) -> Result<Duration, DurationError> {
+    let input = input.trim().to_lowercase();
+
+    if input.is_empty() {
+        return Err(DurationError::Empty);
+    }
+
+    let seconds = if input.contains(':') {
+        parse_clock_duration(&input)?
+    } else {
+        parse_unit_duration(&input)?
+    };
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parses a number of the duration grammar, checking that it only has digits
+fn parse_duration_number(part: &str) -> Result<u64, DurationError> {
+    if let Some(c) = part.chars().find(|c| !c.is_ascii_digit()) {
+        return Err(DurationError::InvalidCharacter(c));
     }
 
-    // Otherwise, it's a duration in the format `hh:mm:ss`
-    let captures = DURATION_REGEX.captures(input)?;
+    part.parse::<u64>().map_err(|_| DurationError::Overflow)
+}
+
+/// Parses a duration in the format `hh:mm:ss` or `mm:ss` to seconds
+fn parse_clock_duration(input: &str) -> Result<u64, DurationError> {
+    let parts = input.split(':').collect::<Vec<_>>();
+
+    let names = match parts.len() {
+        2 => ["minutos", "segundos"].as_slice(),
+        3 => ["horas", "minutos", "segundos"].as_slice(),
+        _ => return Err(DurationError::TooManyParts),
+    };
+
+    let mut seconds: u64 = 0;
+
+    for (i, (part, name)) in parts.iter().zip(names).enumerate() {
+        if part.is_empty() {
+            return Err(DurationError::MissingNumber(':'));
+        }
+
+        let value = parse_duration_number(part)?;
+
+        // Only the first part can go over 59
+        if i > 0 && value >= 60 {
+            return Err(DurationError::OutOfRange(name));
+        }
+
+        seconds = seconds
+            .checked_mul(60)
+            .and_then(|s| s.checked_add(value))
+            .ok_or(DurationError::Overflow)?;
+    }
+
+    Ok(seconds)
+}
+
+/// Parses a duration in the format `1h2m3s` to seconds
+fn parse_unit_duration(input: &str) -> Result<u64, DurationError> {
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+
+    for c in input.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
 
-    let mut result = Duration::new(0, 0);
+        let multiplier = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            c if c.is_alphabetic() => return Err(DurationError::UnknownUnit(c)),
+            c => return Err(DurationError::InvalidCharacter(c)),
+        };
+
+        if number.is_empty() {
+            return Err(DurationError::MissingNumber(c));
+        }
+
+        seconds = parse_duration_number(&number)?
+            .checked_mul(multiplier)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or(DurationError::Overflow)?;
+
+        number.clear();
+    }
+
+    // A trailing number without a unit is in seconds
+    if !number.is_empty() {
+        seconds = seconds
+            .checked_add(parse_duration_number(&number)?)
+            .ok_or(DurationError::Overflow)?;
+    }
 
-    if let Some(hours) = captures.get(1) {
-        let hours = hours.as_str().parse::<u64>().unwrap();
+    Ok(seconds)
+}
+
+/// A time to seek to, either absolute or relative to the current position
+#[derive(Debug, Clone, Copy)]
+pub(super) enum SeekTarget {
+    Absolute(Duration),
+    Forward(Duration),
+    Backward(Duration),
+}
+
+/// Parses a seek target, which is a duration optionally prefixed by `+` or `-`
+///
+/// ## Arguments
+///
+/// * `input` - The string to parse, e.g. `1:30`, `+30` or `-1:00`
+///
+/// ## Returns
+///
+/// * `Ok(SeekTarget)` - The parsed target
+/// * `Err(DurationError)` - The duration was not in the correct format
+pub(super) fn parse_seek_target(input: &str) -> Result<SeekTarget, DurationError> {
+    let input = input.trim();
 
-        result += Duration::from_secs(hours * 3600);
+    if let Some(offset) = input.strip_prefix('+') {
+        return parse_duration(offset).map(SeekTarget::Forward);
     }
 
-    let minutes = captures.get(2)?.as_str().parse::<u64>().unwrap();
+    if let Some(offset) = input.strip_prefix('-') {
+        return parse_duration(offset).map(SeekTarget::Backward);
+    }
 
-    result += Duration::from_secs(minutes * 60);
+    parse_duration(input).map(SeekTarget::Absolute)
+}
 
-    let seconds = captures.get(3)?.as_str().parse::<u64>().unwrap();
+/// Seeks a track, clamping the new position between the start and the end of the track
+///
+/// ## Arguments
+///
+/// * `track` - The track to seek
+/// * `target` - The time to seek to
+/// * `filters` - The active filters, which change the speed of the track
+///
+/// ## Returns
+///
+/// * `Ok((Duration, Duration))` - The time of the song before and after seeking
+/// * `Err(MusicCommandError)` - The track could not be seeked
+pub(super) async fn seek_track(
+    track: &TrackHandle,
+    target: SeekTarget,
+    filters: &[AudioFilter],
+) -> Result<(Duration, Duration), MusicCommandError> {
+    let duration = track
+        .metadata()
+        .duration
+        .ok_or(MusicCommandError::SeekFailed)?;
+
+    let old_position = track
+        .get_info()
+        .await
+        .map_err(|_| MusicCommandError::NoSongPlaying)?
+        .position;
+    let old_position = source_time(filters, old_position);
+
+    let new_position = match target {
+        SeekTarget::Absolute(position) => position,
+        SeekTarget::Forward(offset) => old_position + offset,
+        SeekTarget::Backward(offset) => old_position.saturating_sub(offset),
+    }
+    .min(duration);
 
-    result += Duration::from_secs(seconds);
+    track
+        .seek_time(output_time(filters, new_position))
+        .map_err(|_| MusicCommandError::SeekFailed)?;
 
-    Some(result)
+    Ok((old_position, new_position))
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -260,56 +955,249 @@ impl TypeMapKey for TrackRequester {
     type Value = UserId;
 }
 
+/// Marks a track that was replaced by a new copy of itself, so its end
+/// is not handled as if it finished or was skipped
+pub(super) struct TrackReplaced;
+
+impl TypeMapKey for TrackReplaced {
+    type Value = ();
+}
+
+/// The text channel a track was requested in
 pub(super) struct TrackChannel;
 
 impl TypeMapKey for TrackChannel {
     type Value = ChannelId;
 }
 
-/// Add a song to the queue in a given position
+/// The user that asked for a song, and the text channel they asked in
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Requester {
+    pub(super) user_id: UserId,
+    pub(super) channel_id: ChannelId,
+}
+
+impl From<&Invocation<'_>> for Requester {
+    fn from(invocation: &Invocation<'_>) -> Self {
+        Self {
+            user_id: invocation.author().id,
+            channel_id: invocation.channel_id(),
+        }
+    }
+}
+
+/// Returns who requested a track and where, as set by `insert_song`
+async fn get_track_requester(track: &TrackHandle) -> Result<Requester, MusicCommandError> {
+    let typemap = track.typemap().read().await;
+
+    Ok(Requester {
+        user_id: *typemap
+            .get::<TrackRequester>()
+            .ok_or(MusicCommandError::Generic)?,
+        channel_id: *typemap
+            .get::<TrackChannel>()
+            .ok_or(MusicCommandError::Generic)?,
+    })
+}
+
+/// Returns the volume new tracks should play at
 ///
 /// ## Arguments
 ///
-/// * `requester` - The user who requested the song
-/// * `handler_lock` - A lock to the songbird handler
-/// * `source` - The song to add to the queue
-/// * `position` - The position to add the song to
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild to get the volume of
 ///
 /// ## Returns
 ///
-/// * `Ok(usize)` - The index of the song in the queue
-/// * `Err(&str)` - The song was not added to the queue
-pub(super) async fn insert_song(
+/// * `u32` - The volume set with the `volume` command, or the `default_volume` of the guild
+pub(super) async fn get_volume(ctx: &Context, guild_id: GuildId) -> u32 {
+    match get_music_state(ctx, guild_id).await.volume {
+        Some(volume) => volume,
+        None => get_default_volume(ctx, guild_id).await,
+    }
+}
+
+/// The limits set for the queue of a guild
+/// (`max_track_minutes`, `max_user_tracks` and `max_queue_length`)
+#[derive(Debug, Clone, Copy)]
+struct QueueLimits {
+    max_track_minutes: Option<u64>,
+    max_queue_length: Option<usize>,
+    max_user_tracks: Option<usize>,
+}
+
+/// Reads the limits set for the queue of a guild
+async fn get_queue_limits(ctx: &Context, guild_id: GuildId) -> QueueLimits {
+    QueueLimits {
+        max_track_minutes: get_max_track_minutes(ctx, guild_id).await,
+        max_queue_length: get_max_queue_length(ctx, guild_id).await,
+        max_user_tracks: get_max_user_tracks(ctx, guild_id).await,
+    }
+}
+
+/// Checks that a song can be added to the queue without going over the limits
+/// set for the guild. Songs of unknown duration are rejected when there is a
+/// maximum duration, since they could be as long as they want
+///
+/// ## Arguments
+///
+/// * `limits` - The limits of the guild
+/// * `requester` - The user who requested the song
+/// * `queue` - The queue the song would be added to
+/// * `duration` - The duration of the song to add
+///
+/// ## Returns
+///
+/// * `Ok(())` - The song can be added
+/// * `Err(MusicCommandError)` - The limit that would be exceeded
+async fn check_queue_limits(
+    limits: &QueueLimits,
     requester: UserId,
+    queue: &TrackQueue,
+    duration: Option<Duration>,
+) -> Result<(), MusicCommandError> {
+    if let Some(max_minutes) = limits.max_track_minutes {
+        let duration = duration.ok_or(MusicCommandError::UnknownDuration(max_minutes))?;
+
+        if duration > Duration::from_secs(max_minutes * 60) {
+            return Err(MusicCommandError::TrackTooLong(max_minutes));
+        }
+    }
+
+    if let Some(max_length) = limits.max_queue_length {
+        if queue.len() >= max_length {
+            return Err(MusicCommandError::QueueFull(max_length));
+        }
+    }
+
+    if let Some(max_tracks) = limits.max_user_tracks {
+        let mut count = 0;
+
+        for track in queue.current_queue() {
+            if get_requester(&track).await == Some(requester) {
+                count += 1;
+            }
+        }
+
+        if count >= max_tracks {
+            return Err(MusicCommandError::UserQueueLimit(max_tracks));
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a song to the queue in a given position
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `requester` - The user who requested the song and the channel they did it in
+/// * `handler_lock` - A lock to the songbird handler
+/// * `source` - The song to add to the queue
+/// * `start` - The time to start playing the song at
+/// * `position` - The position to add the song to
+///
+/// ## Returns
+///
+/// * `Ok(usize)` - The index of the song in the queue
+/// * `Err(&str)` - The song was not added to the queue
+pub(super) async fn insert_song(
+    ctx: &Context,
+    guild_id: GuildId,
+    requester: Requester,
+    handler_lock: Arc<Mutex<Call>>,
+    source: Input,
+    start: Option<Duration>,
+    position: QueuePosition,
+) -> Result<usize, MusicCommandError> {
+    let limits = get_queue_limits(ctx, guild_id).await;
+
+    enqueue_song(
+        ctx,
+        guild_id,
+        requester,
+        handler_lock,
+        source,
+        start,
+        position,
+        Some(limits),
+    )
+    .await
+}
+
+/// Add a song to the queue in a given position, checking the queue limits if given
+#[allow(clippy::too_many_arguments)]
+async fn enqueue_song(
+    ctx: &Context,
+    guild_id: GuildId,
+    requester: Requester,
     handler_lock: Arc<Mutex<Call>>,
     source: Input,
+    start: Option<Duration>,
     position: QueuePosition,
+    limits: Option<QueueLimits>,
 ) -> Result<usize, MusicCommandError> {
+    // The settings are read before locking the call, since other tasks
+    // lock the bot data while holding it
+    let volume = get_volume(ctx, guild_id).await;
+    let fair_queue = get_fair_queue(ctx, guild_id).await;
+
     let mut handler = handler_lock.lock().await;
 
+    if let Some(limits) = limits {
+        let duration = source.metadata.duration;
+        check_queue_limits(&limits, requester.user_id, handler.queue(), duration).await?;
+    }
+
+    if let QueuePosition::Index(index) = position {
+        if index == 0 || index > handler.queue().len() {
+            return Err(MusicCommandError::InvalidQueueIndex);
+        }
+    }
+
     // Add the song to the queue
     let handle = handler.enqueue_source(source);
 
+    handle
+        .set_volume(volume as f32 / 100.0)
+        .map_err(|_| MusicCommandError::Generic)?;
+
     // Add custom metadata to the song
     {
         let mut typemap = handle.typemap().write().await;
 
-        typemap.insert::<TrackRequester>(requester);
-        typemap.insert::<TrackChannel>(ChannelId(handler.current_channel().unwrap().0));
+        typemap.insert::<TrackRequester>(requester.user_id);
+        typemap.insert::<TrackChannel>(requester.channel_id);
     }
 
+    handle
+        .add_event(
+            Event::Delayed(Duration::ZERO),
+            TrackStartHandler {
+                ctx: ctx.clone(),
+                guild_id,
+                start,
+            },
+        )
+        .map_err(|_| MusicCommandError::Generic)?;
+
     // Modify the queue if necessary
     let queue = handler.queue();
 
-    match position {
+    let index = match position {
+        QueuePosition::Last if fair_queue => {
+            fair_reorder(queue).await;
+
+            queue
+                .current_queue()
+                .iter()
+                .position(|track| track.uuid() == handle.uuid())
+                .ok_or(MusicCommandError::Generic)
+        }
         QueuePosition::Last => Ok(queue.len() - 1),
         QueuePosition::Index(index) => {
-            let queue = handler.queue();
-
-            if index >= queue.len() || index == 0 {
-                return Err(MusicCommandError::InvalidQueueIndex);
-            }
-
             queue.modify_queue(move |q| {
                 let song = q.remove(q.len() - 1).unwrap();
                 q.insert(index, song);
@@ -317,7 +1205,300 @@ pub(super) async fn insert_song(
 
             Ok(index)
         }
+    };
+
+    index
+}
+
+/// Shuffles the songs in the queue, except the one that is playing
+///
+/// ## Arguments
+///
+/// * `queue` - The queue to shuffle
+pub(super) fn shuffle_queue(queue: &TrackQueue) {
+    queue.modify_queue(|q| {
+        q.make_contiguous()[1..].shuffle(&mut rand::thread_rng());
+    });
+}
+
+/// Reorders the songs in the queue so the requesters take turns, except the
+/// one that is playing. The requester of the playing song goes last in each turn.
+///
+/// ## Arguments
+///
+/// * `queue` - The queue to reorder
+pub(super) async fn fair_reorder(queue: &TrackQueue) {
+    let tracks = queue.current_queue();
+
+    let Some((first, rest)) = tracks.split_first() else {
+        return;
+    };
+
+    let current_requester = first
+        .typemap()
+        .read()
+        .await
+        .get::<TrackRequester>()
+        .copied();
+
+    let mut requesters = Vec::with_capacity(rest.len());
+
+    for track in rest {
+        let requester = track
+            .typemap()
+            .read()
+            .await
+            .get::<TrackRequester>()
+            .copied();
+        requesters.push(requester);
+    }
+
+    let ranks: HashMap<_, _> = rest
+        .iter()
+        .map(TrackHandle::uuid)
+        .zip(fair_ranks(current_requester, &requesters))
+        .collect();
+
+    queue.modify_queue(|q| {
+        q.make_contiguous()[1..]
+            .sort_by_key(|track| ranks.get(&track.uuid()).copied().unwrap_or(usize::MAX));
+    });
+}
+
+/// Returns the place of each song in a fair order, where the requesters take
+/// turns and the requester of the playing song goes last in each turn
+///
+/// ## Arguments
+///
+/// * `current_requester` - The requester of the playing song
+/// * `requesters` - The requester of each queued song, in queue order
+///
+/// ## Returns
+///
+/// * `Vec<usize>` - The rank of each song, lower ranks play first
+fn fair_ranks(current_requester: Option<UserId>, requesters: &[Option<UserId>]) -> Vec<usize> {
+    // Requesters in order of appearance, with the current one last
+    let mut turns: Vec<Option<UserId>> = requesters.iter().copied().unique().collect();
+
+    if let Some(index) = turns.iter().position(|r| *r == current_requester) {
+        let requester = turns.remove(index);
+        turns.push(requester);
     }
+
+    // The n-th song of each requester plays in the n-th round
+    let mut counts = vec![0; turns.len()];
+
+    requesters
+        .iter()
+        .map(|requester| {
+            let turn = turns.iter().position(|r| r == requester).unwrap();
+            let round = counts[turn];
+            counts[turn] += 1;
+
+            round * turns.len() + turn
+        })
+        .collect()
+}
+
+/// Adds a new copy of a track to the queue, keeping its custom metadata.
+/// Used for tracks that already ended.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `handler_lock` - A lock to the songbird handler
+/// * `track` - The track to copy
+/// * `position` - The position to add the copy to
+///
+/// ## Returns
+///
+/// * `Ok(usize)` - The index of the copy in the queue
+/// * `Err(MusicCommandError)` - The track could not be recreated
+pub(super) async fn reinsert_track(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+    track: &TrackHandle,
+    position: QueuePosition,
+) -> Result<usize, MusicCommandError> {
+    let limits = get_queue_limits(ctx, guild_id).await;
+
+    copy_track(ctx, guild_id, handler_lock, track, position, Some(limits)).await
+}
+
+/// Sends a track that ended back to the end of the queue, when the whole
+/// queue loops. The queue limits are not checked, since the track was
+/// already in the queue
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `handler_lock` - A lock to the songbird handler
+/// * `track` - The track to send back
+///
+/// ## Returns
+///
+/// * `Ok(usize)` - The index of the copy in the queue
+/// * `Err(MusicCommandError)` - The track could not be recreated
+pub(super) async fn requeue_track(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+    track: &TrackHandle,
+) -> Result<usize, MusicCommandError> {
+    copy_track(
+        ctx,
+        guild_id,
+        handler_lock,
+        track,
+        QueuePosition::Last,
+        None,
+    )
+    .await
+}
+
+/// Adds a new copy of a track to the queue, checking the queue limits if given
+async fn copy_track(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+    track: &TrackHandle,
+    position: QueuePosition,
+    limits: Option<QueueLimits>,
+) -> Result<usize, MusicCommandError> {
+    let url = track
+        .metadata()
+        .source_url
+        .clone()
+        .ok_or(MusicCommandError::FailedVideoSearch)?;
+
+    let requester = get_track_requester(track).await?;
+
+    let source = filtered_source(ctx, guild_id, url).await?;
+
+    let index = enqueue_song(
+        ctx,
+        guild_id,
+        requester,
+        handler_lock,
+        source.into(),
+        None,
+        position,
+        limits,
+    )
+    .await?;
+
+    // Songs that are played again are worth keeping in the cache
+    cache_song(ctx, track.metadata()).await;
+
+    Ok(index)
+}
+
+/// Plays the current song again from a time of the song, building its source
+/// again so it picks up the active filters.
+///
+/// songbird only builds a source again when seeking back, so when the new
+/// position is ahead the song is replaced by a copy of itself, seeked before
+/// it starts playing.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `handler_lock` - A lock to the songbird handler
+/// * `track` - The current song
+/// * `filters` - The active filters
+/// * `time` - The time of the song to play from
+///
+/// ## Returns
+///
+/// * `Ok(())` - The song was restarted
+/// * `Err(MusicCommandError)` - The song could not be restarted
+pub(super) async fn restart_current_track(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+    track: &TrackHandle,
+    filters: &[AudioFilter],
+    time: Duration,
+) -> Result<(), MusicCommandError> {
+    let old_position = track
+        .get_info()
+        .await
+        .map_err(|_| MusicCommandError::NoSongPlaying)?
+        .position;
+
+    let position = output_time(filters, time);
+
+    if position < old_position {
+        return track
+            .seek_time(position)
+            .map_err(|_| MusicCommandError::SeekFailed);
+    }
+
+    let metadata = track.metadata().clone();
+    let url = metadata
+        .source_url
+        .clone()
+        .ok_or(MusicCommandError::FailedVideoSearch)?;
+    let requester = get_track_requester(track).await?;
+
+    let source = lazy_source(ctx, guild_id, url, metadata).await?;
+
+    enqueue_song(
+        ctx,
+        guild_id,
+        requester,
+        handler_lock.clone(),
+        source.into(),
+        None,
+        QueuePosition::Index(1),
+        None,
+    )
+    .await?;
+
+    track.typemap().write().await.insert::<TrackReplaced>(());
+
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue();
+
+    let copy = queue
+        .current_queue()
+        .get(1)
+        .cloned()
+        .ok_or(MusicCommandError::Generic)?;
+
+    // The copy hasn't been read yet, so seeking builds its source at the position
+    copy.seek_time(position)
+        .map_err(|_| MusicCommandError::SeekFailed)?;
+
+    queue.skip().map_err(|_| MusicCommandError::Generic)
+}
+
+/// Plays the song at index 1 of the queue right away, leaving the current one
+/// paused right after it
+///
+/// ## Arguments
+///
+/// * `handler_lock` - A lock to the songbird handler
+pub(super) async fn play_next_now(handler_lock: Arc<Mutex<Call>>) -> Result<(), MusicCommandError> {
+    let handler = handler_lock.lock().await;
+
+    let queue = handler.queue();
+
+    if queue.len() < 2 {
+        return Err(MusicCommandError::InvalidQueueIndex);
+    }
+
+    queue.pause().map_err(|_| MusicCommandError::Generic)?;
+
+    queue.modify_queue(|q| q.swap(0, 1));
+
+    queue.resume().map_err(|_| MusicCommandError::Generic)?;
+
+    Ok(())
 }
 
 /// Removes a song from the queue
@@ -343,27 +1524,897 @@ pub(super) async fn remove_song(
         return Err(MusicCommandError::InvalidQueueIndex);
     }
 
-    let mut removed_title = None;
+    let removed = queue.modify_queue(|q| q.remove(index).unwrap().handle());
 
-    queue.modify_queue(|q| {
-        removed_title = q.remove(index).unwrap().metadata().title.clone();
+    // Stopped so its source is freed
+    removed.stop().ok();
+
+    removed
+        .metadata()
+        .title
+        .clone()
+        .ok_or(MusicCommandError::InvalidQueueIndex)
+}
+
+/// The songs picked by the `remove` command
+#[derive(Debug, Clone)]
+pub(super) enum RemovalTarget {
+    /// Positions of the queue, each one being a single position or a span like `3-7`
+    Positions(Vec<RangeInclusive<usize>>),
+    /// Every song requested by a user
+    Requester(UserId),
+}
+
+/// Parses a position (`5`) or a span of positions (`3-7`) of the queue
+fn parse_position_range(arg: &str) -> Result<RangeInclusive<usize>, MusicCommandError> {
+    let parse_position = |position: &str| {
+        position
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&position| position > 0)
+            .ok_or(MusicCommandError::InvalidQueueIndex)
+    };
+
+    match arg.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_position(start)?, parse_position(end)?);
+
+            if start > end {
+                return Err(MusicCommandError::InvalidQueueIndex);
+            }
+
+            Ok(start..=end)
+        }
+        None => {
+            let position = parse_position(arg)?;
+            Ok(position..=position)
+        }
+    }
+}
+
+/// Parses the songs to remove from the queue
+///
+/// ## Arguments
+///
+/// * `arg` - Positions and spans (e.g. `3-7` or `2 5 9`), or the mention of a user
+///
+/// ## Returns
+///
+/// * `Ok(RemovalTarget)` - The songs to remove
+/// * `Err(MusicCommandError)` - A position is not valid
+pub(super) fn parse_removal_target(arg: &str) -> Result<RemovalTarget, MusicCommandError> {
+    let arg = arg.trim();
+
+    if let Some(user_id) = parse_username(arg) {
+        return Ok(RemovalTarget::Requester(UserId(user_id)));
+    }
+
+    let positions = arg
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|position| !position.is_empty())
+        .map(parse_position_range)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if positions.is_empty() {
+        return Err(MusicCommandError::InvalidQueueIndex);
+    }
+
+    Ok(RemovalTarget::Positions(positions))
+}
+
+/// Returns whether every song a removal would take out of the queue was requested by a user
+///
+/// ## Arguments
+///
+/// * `handler_lock` - A lock to the songbird handler
+/// * `target` - The songs to remove
+/// * `user_id` - The user that wants to remove them
+///
+/// ## Returns
+///
+/// * `bool` - Whether no song of another user would be removed
+pub(super) async fn removes_only_own_songs(
+    handler_lock: Arc<Mutex<Call>>,
+    target: &RemovalTarget,
+    user_id: UserId,
+) -> bool {
+    let ranges = match target {
+        RemovalTarget::Requester(requester) => return *requester == user_id,
+        RemovalTarget::Positions(ranges) => ranges,
+    };
+
+    let queue = handler_lock.lock().await.queue().current_queue();
+
+    for index in ranges.iter().flat_map(|range| range.clone()) {
+        // Positions out of the queue are reported by the removal itself
+        let Some(track) = queue.get(index) else {
+            continue;
+        };
+
+        if get_requester(track).await != Some(user_id) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Removes several songs from the queue in a single change. The current song
+/// is never removed
+///
+/// ## Arguments
+///
+/// * `handler_lock` - A lock to the songbird handler
+/// * `target` - The songs to remove
+///
+/// ## Returns
+///
+/// * `Ok(Vec<TrackHandle>)` - The removed songs, in the order they were queued
+/// * `Err(MusicCommandError)` - A position is outside of the queue, or no song matched
+pub(super) async fn remove_songs(
+    handler_lock: Arc<Mutex<Call>>,
+    target: &RemovalTarget,
+) -> Result<Vec<TrackHandle>, MusicCommandError> {
+    let handler = handler_lock.lock().await;
+
+    let queue = handler.queue();
+
+    // The requesters are in the typemaps, which can't be read inside `modify_queue`
+    let mut requested = HashSet::new();
+
+    if let RemovalTarget::Requester(user_id) = target {
+        for track in queue.current_queue().iter().skip(1) {
+            if get_requester(track).await == Some(*user_id) {
+                requested.insert(track.uuid());
+            }
+        }
+    }
+
+    let removed = queue.modify_queue(|q| {
+        let indices: BTreeSet<usize> = match target {
+            RemovalTarget::Positions(ranges) => {
+                if ranges.iter().any(|range| *range.end() >= q.len()) {
+                    return Err(MusicCommandError::InvalidQueueIndex);
+                }
+
+                ranges.iter().flat_map(|range| range.clone()).collect()
+            }
+            RemovalTarget::Requester(_) => q
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter(|(_, track)| requested.contains(&track.uuid()))
+                .map(|(index, _)| index)
+                .collect(),
+        };
+
+        if indices.is_empty() {
+            return Err(MusicCommandError::NoSongsToRemove);
+        }
+
+        // Going backwards keeps the positions that are left valid
+        let mut removed = indices
+            .iter()
+            .rev()
+            .filter_map(|&index| q.remove(index))
+            .map(|queued| queued.handle())
+            .collect::<Vec<_>>();
+
+        removed.reverse();
+
+        Ok(removed)
+    })?;
+
+    for track in &removed {
+        track.stop().ok();
+    }
+
+    Ok(removed)
+}
+
+/// Removes the songs that are already in the queue, keeping the first one.
+/// Songs are compared like in the cache, so different links to the same
+/// video count as the same song
+///
+/// ## Arguments
+///
+/// * `queue` - The queue to remove the songs from
+///
+/// ## Returns
+///
+/// * `Vec<TrackHandle>` - The removed songs, in the order they were queued
+pub(super) fn remove_duplicates(queue: &TrackQueue) -> Vec<TrackHandle> {
+    let removed = queue.modify_queue(|q| {
+        let mut seen = HashSet::new();
+        let mut removed = Vec::new();
+
+        // The current song is always kept, but later copies of it are not
+        let tracks = q.drain(..).collect::<Vec<_>>();
+
+        for (index, track) in tracks.into_iter().enumerate() {
+            let is_new = match &track.metadata().source_url {
+                Some(url) => seen.insert(song_key(url)),
+                None => true,
+            };
+
+            if is_new || index == 0 {
+                q.push_back(track);
+            } else {
+                removed.push(track.handle());
+            }
+        }
+
+        removed
     });
 
-    removed_title.ok_or(MusicCommandError::InvalidQueueIndex)
+    for track in &removed {
+        track.stop().ok();
+    }
+
+    removed
+}
+
+lazy_static! {
+    static ref PLAYLIST_REGEX: Regex =
+        Regex::new(r"^https?://(?:www\.|m\.|music\.)?youtube\.com/playlist\?(?:.*&)?list=")
+            .unwrap();
+}
+
+/// Checks if a query is a link to a playlist
+pub(super) fn is_playlist_url(query: &str) -> bool {
+    PLAYLIST_REGEX.is_match(query.trim())
+}
+
+/// The result of adding a playlist to the queue
+pub(super) struct PlaylistInsertion {
+    /// The amount of songs added
+    pub(super) added: usize,
+    /// The total duration of the added songs
+    pub(super) duration: Duration,
+    /// The titles (or links) of the songs that could not be added
+    pub(super) skipped: Vec<String>,
+}
+
+/// A song to add to the queue, with what is known about it before it's loaded
+#[derive(Debug, Clone)]
+pub(super) struct QueueEntry {
+    pub(super) title: String,
+    pub(super) url: String,
+    pub(super) duration: Option<Duration>,
+}
+
+impl QueueEntry {
+    /// Returns the metadata the song is queued with until it's loaded
+    pub(super) fn metadata(&self) -> Metadata {
+        Metadata {
+            title: Some(self.title.clone()),
+            source_url: Some(self.url.clone()),
+            duration: self.duration,
+            thumbnail: youtube_id(&self.url)
+                .map(|id| format!("https://i.ytimg.com/vi/{id}/hqdefault.jpg")),
+            ..Default::default()
+        }
+    }
+}
+
+/// An entry of a playlist or a search, as listed by yt-dlp without loading it
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct FlatEntry {
+    pub(super) url: String,
+    pub(super) title: Option<String>,
+    pub(super) uploader: Option<String>,
+    pub(super) channel: Option<String>,
+    /// The duration of the entry, in seconds
+    pub(super) duration: Option<f64>,
+    /// Whether the entry can be watched, e.g. `public` or `private`
+    pub(super) availability: Option<String>,
 }
 
-/// Searches for a song in youtube
+impl FlatEntry {
+    /// Whether the entry can't be played, like private or deleted videos
+    pub(super) fn is_unavailable(&self) -> bool {
+        let restricted = matches!(
+            self.availability.as_deref(),
+            Some("private" | "premium_only" | "subscriber_only" | "needs_auth")
+        );
+        let removed = matches!(
+            self.title.as_deref(),
+            Some("[Private video]" | "[Deleted video]")
+        );
+
+        restricted || removed
+    }
+
+    /// The title of the entry, or its link if it has none
+    pub(super) fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.url)
+    }
+
+    pub(super) fn display_uploader(&self) -> &str {
+        self.uploader
+            .as_deref()
+            .or(self.channel.as_deref())
+            .unwrap_or("?")
+    }
+}
+
+/// Lists the entries of a playlist or search without loading them
+///
+/// ## Arguments
+///
+/// * `target` - The link to the playlist, or a yt-dlp search (e.g. `ytsearch5:query`)
+/// * `limit` - The maximum amount of entries to list
+///
+/// ## Returns
+///
+/// * `Vec<FlatEntry>` - The entries, empty if yt-dlp failed
+async fn get_flat_entries(target: &str, limit: usize) -> Vec<FlatEntry> {
+    let output = tokio::process::Command::new("yt-dlp")
+        .args([
+            "--flat-playlist",
+            "--ignore-errors",
+            "--dump-json",
+            "--playlist-end",
+        ])
+        .arg(limit.to_string())
+        .arg(target)
+        .output()
+        .await;
+
+    let Ok(output) = output else {
+        return vec![];
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+lazy_static! {
+    static ref YOUTUBE_ID_REGEX: Regex =
+        Regex::new(r"(?:[?&]v=|youtu\.be/|/shorts/)([\w-]{11})").unwrap();
+}
+
+/// The amount of related songs listed when looking for one to autoplay
+const AUTOPLAY_CANDIDATES: usize = 15;
+
+/// Returns the id of the video of a youtube link
+pub(super) fn youtube_id(url: &str) -> Option<&str> {
+    YOUTUBE_ID_REGEX
+        .captures(url)
+        .and_then(|captures| captures.get(1))
+        .map(|id| id.as_str())
+}
+
+/// Returns a key that identifies the song of a link, so the same song can be
+/// recognized under different links
+fn song_key(url: &str) -> String {
+    youtube_id(url).unwrap_or(url).to_string()
+}
+
+/// Adds a song related to a finished track to the queue, requested by the bot.
+/// Songs in the history of the guild are skipped.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `handler_lock` - A lock to the songbird handler
+/// * `track` - The track to look for related songs of
+///
+/// ## Returns
+///
+/// * `Ok(usize)` - The index of the added song in the queue
+/// * `Err(MusicCommandError)` - No related song could be added
+pub(super) async fn autoplay_related(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: Arc<Mutex<Call>>,
+    track: &TrackHandle,
+) -> Result<usize, MusicCommandError> {
+    let metadata = track.metadata().clone();
+    let url = metadata.source_url.unwrap_or_default();
+
+    // The youtube mix of a video has its related songs
+    let mut candidates = match YOUTUBE_ID_REGEX.captures(&url) {
+        Some(captures) => {
+            let id = &captures[1];
+            let mix = format!("https://www.youtube.com/watch?v={id}&list=RD{id}");
+
+            get_flat_entries(&mix, AUTOPLAY_CANDIDATES).await
+        }
+        None => vec![],
+    };
+
+    // Otherwise, look for songs of the same artist
+    if candidates.is_empty() {
+        let title = metadata.title.unwrap_or_default();
+        let uploader = metadata.artist.or(metadata.channel).unwrap_or_default();
+        let search = format!("ytsearch{AUTOPLAY_CANDIDATES}:{title} {uploader}");
+
+        candidates = get_flat_entries(&search, AUTOPLAY_CANDIDATES).await;
+    }
+
+    let recent: HashSet<String> = get_music_state(ctx, guild_id)
+        .await
+        .history
+        .iter()
+        .filter_map(|track| track.metadata().source_url.as_deref().map(song_key))
+        .chain([song_key(&url)])
+        .collect();
+
+    let entry = candidates
+        .into_iter()
+        .find(|entry| !recent.contains(&song_key(&entry.url)))
+        .ok_or(MusicCommandError::FailedVideoSearch)?;
+
+    let source = filtered_source(ctx, guild_id, entry.url).await?;
+
+    // Announce it where the finished track was requested
+    let requester = Requester {
+        user_id: ctx.cache.current_user_id(),
+        channel_id: get_track_requester(track).await?.channel_id,
+    };
+
+    insert_song(
+        ctx,
+        guild_id,
+        requester,
+        handler_lock,
+        source.into(),
+        None,
+        QueuePosition::Last,
+    )
+    .await
+}
+
+/// Searches for songs in youtube without loading them
 ///
 /// ## Arguments
 ///
 /// * `query` - The query to search for
+/// * `count` - The amount of results
 ///
 /// ## Returns
 ///
-/// * `Ok(Input)` - The song was found
-/// * `Err(MusicCommandError)` - The song was not found
-pub(super) async fn search_song(query: &str) -> Result<Restartable, MusicCommandError> {
-    Restartable::ytdl_search(query, true)
+/// * `Ok(Vec<FlatEntry>)` - The results of the search
+/// * `Err(MusicCommandError)` - Nothing was found
+pub(super) async fn search_songs(
+    query: &str,
+    count: usize,
+) -> Result<Vec<FlatEntry>, MusicCommandError> {
+    let results = get_flat_entries(&format!("ytsearch{count}:{}", query.trim()), count).await;
+
+    if results.is_empty() {
+        return Err(MusicCommandError::FailedVideoSearch);
+    }
+
+    Ok(results)
+}
+
+/// Adds every song of a playlist to the queue, starting at a given position.
+/// Songs that are not available are skipped.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `requester` - The user who requested the playlist
+/// * `handler_lock` - A lock to the songbird handler
+/// * `url` - The link to the playlist
+/// * `position` - The position to add the first song to
+///
+/// ## Returns
+///
+/// * `Ok(PlaylistInsertion)` - A summary of the songs that were added
+/// * `Err(MusicCommandError)` - The playlist could not be read
+pub(super) async fn insert_playlist(
+    ctx: &Context,
+    guild_id: GuildId,
+    requester: Requester,
+    handler_lock: Arc<Mutex<Call>>,
+    url: &str,
+    position: QueuePosition,
+) -> Result<PlaylistInsertion, MusicCommandError> {
+    let entries = get_flat_entries(url.trim(), *PLAYLIST_MAX_ITEMS).await;
+
+    if entries.is_empty() {
+        return Err(MusicCommandError::FailedPlaylistSearch);
+    }
+
+    let (unavailable, entries): (Vec<_>, Vec<_>) =
+        entries.into_iter().partition(FlatEntry::is_unavailable);
+
+    let entries = entries.into_iter().map(|entry| QueueEntry {
+        title: entry.display_title().to_string(),
+        duration: entry.duration.map(Duration::from_secs_f64),
+        url: entry.url,
+    });
+
+    let mut result =
+        insert_entries(ctx, guild_id, requester, handler_lock, entries, position).await;
+
+    result.skipped.extend(
+        unavailable
+            .iter()
+            .map(|entry| entry.display_title().to_string()),
+    );
+
+    Ok(result)
+}
+
+/// Adds a list of songs to the queue, starting at a given position. The songs
+/// are queued with the metadata of their entries and only loaded when they start.
+/// Songs that can't be added are skipped.
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the queue belongs to
+/// * `requester` - The user who requested the songs
+/// * `handler_lock` - A lock to the songbird handler
+/// * `entries` - The songs to add
+/// * `position` - The position to add the first song to
+///
+/// ## Returns
+///
+/// * `PlaylistInsertion` - A summary of the songs that were added
+pub(super) async fn insert_entries(
+    ctx: &Context,
+    guild_id: GuildId,
+    requester: Requester,
+    handler_lock: Arc<Mutex<Call>>,
+    entries: impl IntoIterator<Item = QueueEntry>,
+    position: QueuePosition,
+) -> PlaylistInsertion {
+    let mut result = PlaylistInsertion {
+        added: 0,
+        duration: Duration::ZERO,
+        skipped: vec![],
+    };
+
+    for entry in entries {
+        let Ok(source) = lazy_source(ctx, guild_id, entry.url.clone(), entry.metadata()).await
+        else {
+            result.skipped.push(entry.title);
+            continue;
+        };
+
+        let source: Input = source.into();
+        let duration = source.metadata.duration.unwrap_or_default();
+
+        // Keep the songs of the playlist together when inserting in the middle
+        let position = match position {
+            QueuePosition::Last => QueuePosition::Last,
+            QueuePosition::Index(index) => QueuePosition::Index(index + result.added),
+        };
+
+        match insert_song(
+            ctx,
+            guild_id,
+            requester,
+            handler_lock.clone(),
+            source,
+            None,
+            position,
+        )
         .await
-        .map_err(|_| MusicCommandError::FailedVideoSearch)
+        {
+            Ok(_) => {
+                result.added += 1;
+                result.duration += duration;
+            }
+            Err(_) => result.skipped.push(entry.title),
+        }
+    }
+
+    result
+}
+
+lazy_static! {
+    static ref URL_REGEX: Regex = Regex::new(r"^https?://(?:www\.|m\.)?([^/?#:]+)").unwrap();
+    static ref TIMESTAMP_REGEX: Regex = Regex::new(r"[?&#](?:t|start)=([0-9hms:]+)").unwrap();
+}
+
+/// The hosts that can be played by link
+const SUPPORTED_HOSTS: [&str; 6] = [
+    "youtube.com",
+    "youtu.be",
+    "music.youtube.com",
+    "soundcloud.com",
+    "bandcamp.com",
+    "vimeo.com",
+];
+
+/// Checks if the host of a link can be played. Subdomains of the supported
+/// hosts are also allowed (e.g. `artist.bandcamp.com`).
+fn is_supported_host(host: &str) -> bool {
+    let host = host.to_lowercase();
+
+    SUPPORTED_HOSTS
+        .iter()
+        .any(|supported| host == *supported || host.ends_with(&format!(".{supported}")))
+}
+
+/// Checks if a link can be played
+pub(super) fn is_supported_url(url: &str) -> bool {
+    URL_REGEX
+        .captures(url.trim())
+        .is_some_and(|captures| is_supported_host(&captures[1]))
+}
+
+/// Parses the timestamp of a link (e.g. `?t=90` or `&t=1m30s`)
+///
+/// ## Arguments
+///
+/// * `url` - The link to parse
+///
+/// ## Returns
+///
+/// * `Some(Duration)` - The time the link points to
+/// * `None` - The link has no timestamp
+fn parse_url_timestamp(url: &str) -> Option<Duration> {
+    let timestamp = TIMESTAMP_REGEX.captures(url)?.get(1)?.as_str();
+
+    parse_duration(timestamp)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+}
+
+/// Searches for a song in youtube, or loads it directly if the query is a link
+///
+/// ## Arguments
+///
+/// * `ctx` - The context of the bot
+/// * `guild_id` - The guild the song will play in
+/// * `query` - The query to search for, or the link to the song
+///
+/// ## Returns
+///
+/// * `Ok((Restartable, Option<Duration>))` - The song was found, along with the time
+///   to start playing it at if the link has a timestamp
+/// * `Err(MusicCommandError)` - The song was not found or the link is not supported
+pub(super) async fn search_song(
+    ctx: &Context,
+    guild_id: GuildId,
+    query: &str,
+) -> Result<(Restartable, Option<Duration>), MusicCommandError> {
+    let query = query.trim();
+
+    let Some(captures) = URL_REGEX.captures(query) else {
+        let source = filtered_source(ctx, guild_id, format!("ytsearch1:{query}")).await?;
+
+        return Ok((source, None));
+    };
+
+    if !is_supported_host(&captures[1]) {
+        return Err(MusicCommandError::UnsupportedHost);
+    }
+
+    let source = filtered_source(ctx, guild_id, query.to_string()).await?;
+
+    Ok((source, parse_url_timestamp(query)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), secs(90));
+        assert_eq!(parse_duration("1:30").unwrap(), secs(90));
+        assert_eq!(parse_duration("1:30:00").unwrap(), secs(5400));
+        assert_eq!(parse_duration("90:00").unwrap(), secs(5400));
+        assert_eq!(parse_duration("1h2m3s").unwrap(), secs(3723));
+        assert_eq!(parse_duration("1m30").unwrap(), secs(90));
+        assert_eq!(parse_duration(" 2M ").unwrap(), secs(120));
+    }
+
+    #[test]
+    fn rejects_empty_durations() {
+        assert!(matches!(parse_duration(""), Err(DurationError::Empty)));
+        assert!(matches!(parse_duration("   "), Err(DurationError::Empty)));
+    }
+
+    #[test]
+    fn parses_clock_durations() {
+        assert_eq!(parse_clock_duration("0:00").unwrap(), 0);
+        assert_eq!(parse_clock_duration("1:59").unwrap(), 119);
+        assert_eq!(parse_clock_duration("2:03:04").unwrap(), 7384);
+
+        assert!(matches!(
+            parse_clock_duration("1:60"),
+            Err(DurationError::OutOfRange("segundos"))
+        ));
+        assert!(matches!(
+            parse_clock_duration("1:60:00"),
+            Err(DurationError::OutOfRange("minutos"))
+        ));
+        assert!(matches!(
+            parse_clock_duration("1:2:3:4"),
+            Err(DurationError::TooManyParts)
+        ));
+        assert!(matches!(
+            parse_clock_duration(":30"),
+            Err(DurationError::MissingNumber(':'))
+        ));
+        assert!(matches!(
+            parse_clock_duration("1:"),
+            Err(DurationError::MissingNumber(':'))
+        ));
+        assert!(matches!(
+            parse_clock_duration("1:3a"),
+            Err(DurationError::InvalidCharacter('a'))
+        ));
+        assert!(matches!(
+            parse_clock_duration("999999999999999999:00:00"),
+            Err(DurationError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn parses_unit_durations() {
+        assert_eq!(parse_unit_duration("").unwrap(), 0);
+        assert_eq!(parse_unit_duration("45").unwrap(), 45);
+        assert_eq!(parse_unit_duration("1h 30m").unwrap(), 5400);
+        assert_eq!(parse_unit_duration("2m2m").unwrap(), 240);
+
+        assert!(matches!(
+            parse_unit_duration("1x"),
+            Err(DurationError::UnknownUnit('x'))
+        ));
+        assert!(matches!(
+            parse_unit_duration("m"),
+            Err(DurationError::MissingNumber('m'))
+        ));
+        assert!(matches!(
+            parse_unit_duration("1.5"),
+            Err(DurationError::InvalidCharacter('.'))
+        ));
+        assert!(matches!(
+            parse_unit_duration("99999999999999999999"),
+            Err(DurationError::Overflow)
+        ));
+        assert!(matches!(
+            parse_unit_duration("999999999999999999h"),
+            Err(DurationError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn parses_seek_targets() {
+        assert!(matches!(
+            parse_seek_target("1:30"),
+            Ok(SeekTarget::Absolute(time)) if time == secs(90)
+        ));
+        assert!(matches!(
+            parse_seek_target("+30"),
+            Ok(SeekTarget::Forward(time)) if time == secs(30)
+        ));
+        assert!(matches!(
+            parse_seek_target(" -1:00 "),
+            Ok(SeekTarget::Backward(time)) if time == secs(60)
+        ));
+        assert!(matches!(
+            parse_seek_target("-1m"),
+            Ok(SeekTarget::Backward(time)) if time == secs(60)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_seek_targets() {
+        assert!(matches!(parse_seek_target(""), Err(DurationError::Empty)));
+        assert!(matches!(parse_seek_target("-"), Err(DurationError::Empty)));
+        assert!(matches!(parse_seek_target("+"), Err(DurationError::Empty)));
+        assert!(matches!(
+            parse_seek_target("--5"),
+            Err(DurationError::InvalidCharacter('-'))
+        ));
+        assert!(matches!(
+            parse_seek_target("+1:60"),
+            Err(DurationError::OutOfRange("segundos"))
+        ));
+    }
+
+    #[test]
+    fn parses_url_timestamps() {
+        assert_eq!(
+            parse_url_timestamp("https://youtu.be/dQw4w9WgXcQ?t=90"),
+            Some(secs(90))
+        );
+        assert_eq!(
+            parse_url_timestamp("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1m30s"),
+            Some(secs(90))
+        );
+        assert_eq!(
+            parse_url_timestamp("https://vimeo.com/123456#t=1:30"),
+            Some(secs(90))
+        );
+        assert_eq!(
+            parse_url_timestamp("https://www.youtube.com/watch?v=dQw4w9WgXcQ&start=15"),
+            Some(secs(15))
+        );
+    }
+
+    #[test]
+    fn ignores_missing_or_empty_url_timestamps() {
+        assert_eq!(
+            parse_url_timestamp("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            None
+        );
+        assert_eq!(
+            parse_url_timestamp("https://youtu.be/dQw4w9WgXcQ?t=0"),
+            None
+        );
+        assert_eq!(
+            parse_url_timestamp("https://youtu.be/dQw4w9WgXcQ?t=1:60"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_removal_positions() {
+        let Ok(RemovalTarget::Positions(positions)) = parse_removal_target("3") else {
+            panic!("a single position should be parsed");
+        };
+        assert_eq!(positions, vec![3..=3]);
+
+        let Ok(RemovalTarget::Positions(positions)) = parse_removal_target(" 2 5,9 3-7 ") else {
+            panic!("a list of positions should be parsed");
+        };
+        assert_eq!(positions, vec![2..=2, 5..=5, 9..=9, 3..=7]);
+    }
+
+    #[test]
+    fn parses_removal_requester() {
+        assert!(matches!(
+            parse_removal_target("<@123>"),
+            Ok(RemovalTarget::Requester(UserId(123)))
+        ));
+        assert!(matches!(
+            parse_removal_target("<@!123>"),
+            Ok(RemovalTarget::Requester(UserId(123)))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_removal_targets() {
+        for arg in ["", " , ", "0", "7-3", "3-", "-3", "a", "1-0"] {
+            assert!(
+                matches!(
+                    parse_removal_target(arg),
+                    Err(MusicCommandError::InvalidQueueIndex)
+                ),
+                "`{arg}` should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn fair_ranks_take_turns() {
+        let (a, b, c) = (Some(UserId(1)), Some(UserId(2)), Some(UserId(3)));
+
+        // The requester of the playing song goes last in each turn
+        assert_eq!(fair_ranks(a, &[a, a, b, b]), vec![1, 3, 0, 2]);
+        assert_eq!(fair_ranks(c, &[a, a, a, b]), vec![0, 2, 4, 1]);
+
+        // Songs without a requester take a turn of their own
+        assert_eq!(fair_ranks(None, &[a, None, b]), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn fair_ranks_keep_a_single_requester_in_order() {
+        let a = Some(UserId(1));
+
+        assert_eq!(fair_ranks(a, &[a, a, a]), vec![0, 1, 2]);
+        assert_eq!(fair_ranks(None, &[a, a]), vec![0, 1]);
+        assert!(fair_ranks(a, &[]).is_empty());
+    }
 }
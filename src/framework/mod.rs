@@ -1,7 +1,14 @@
 use lazy_static::lazy_static;
 use serenity::prelude::TypeMapKey;
 
-use crate::commands::{music::settings::MUSIC_CHANNEL_SETTING, sube_baja::SUBE_BAJA_COMMAND};
+use crate::commands::{
+    music::settings::{
+        ANNOUNCE_TRACKS_SETTING, AUTOPLAY_SETTING, DEFAULT_VOLUME_SETTING, DJ_ROLE_SETTING,
+        FAIR_QUEUE_SETTING, MAX_QUEUE_LENGTH_SETTING, MAX_TRACK_MINUTES_SETTING,
+        MAX_USER_TRACKS_SETTING, MUSIC_CHANNEL_SETTING, VOTE_SKIP_RATIO_SETTING,
+    },
+    sube_baja::SUBE_BAJA_COMMAND,
+};
 
 pub mod commands;
 pub mod handler;
@@ -13,7 +20,16 @@ use self::handler::L0C0B0THandler;
 lazy_static! {
     pub static ref L0C0B0T_HANDLER: L0C0B0THandler = L0C0B0THandler::new()
         .command(SUBE_BAJA_COMMAND)
-        .setting(MUSIC_CHANNEL_SETTING);
+        .setting(MUSIC_CHANNEL_SETTING)
+        .setting(FAIR_QUEUE_SETTING)
+        .setting(AUTOPLAY_SETTING)
+        .setting(ANNOUNCE_TRACKS_SETTING)
+        .setting(VOTE_SKIP_RATIO_SETTING)
+        .setting(MAX_TRACK_MINUTES_SETTING)
+        .setting(MAX_USER_TRACKS_SETTING)
+        .setting(MAX_QUEUE_LENGTH_SETTING)
+        .setting(DEFAULT_VOLUME_SETTING)
+        .setting(DJ_ROLE_SETTING);
 }
 
 impl TypeMapKey for L0C0B0T_HANDLER {
@@ -118,7 +118,7 @@ impl Settings {
 
     /// Set the value of a setting for a guild.
     pub fn set(&mut self, id: &GuildId, setting: &str, value: &str) {
-        let map = self.settings.entry(id.0).or_insert_with(HashMap::new);
+        let map = self.settings.entry(id.0).or_default();
         map.insert(setting.to_string(), value.to_string());
     }
 
@@ -1,7 +1,7 @@
 use std::{fmt::Display, str::FromStr};
 
 use serenity::{
-    model::prelude::{ChannelId, Mention},
+    model::prelude::{ChannelId, Mention, RoleId},
     prelude::Mentionable,
 };
 
@@ -36,3 +36,127 @@ impl Display for OptionalChannel {
         }
     }
 }
+
+#[derive(Debug, Clone, Default)]
+pub struct OptionalRole(pub Option<RoleId>);
+
+impl FromStr for OptionalRole {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self(None)),
+            "ninguno" => Ok(Self(None)),
+            _ => {
+                let mention = Mention::from_str(s).map_err(|_| ())?;
+                let role_id = match mention {
+                    Mention::Role(role_id) => role_id,
+                    _ => return Err(()),
+                };
+
+                Ok(Self(Some(role_id)))
+            }
+        }
+    }
+}
+
+impl Display for OptionalRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(role_id) => write!(f, "{}", role_id.mention()),
+            None => write!(f, "ninguno"),
+        }
+    }
+}
+
+/// A fraction between 0 and 1, written as a percentage (e.g. `50%`) or a decimal (e.g. `0.5`)
+#[derive(Debug, Clone, Copy)]
+pub struct Ratio(pub f64);
+
+impl Default for Ratio {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+impl FromStr for Ratio {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = match s.strip_suffix('%') {
+            Some(percentage) => percentage.trim().parse::<f64>().map_err(|_| ())? / 100.0,
+            None => s.parse::<f64>().map_err(|_| ())?,
+        };
+
+        if value > 0.0 && value <= 1.0 {
+            Ok(Self(value))
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Rounded to avoid printing floating point noise
+        write!(f, "{}%", (self.0 * 1000.0).round() / 10.0)
+    }
+}
+
+/// A volume percentage between 0 and `Volume::MAX`
+#[derive(Debug, Clone, Copy)]
+pub struct Volume(pub u32);
+
+impl Volume {
+    pub const MAX: u32 = 200;
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+
+impl FromStr for Volume {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim_end_matches('%').parse::<u32>().map_err(|_| ())?;
+
+        if value <= Self::MAX {
+            Ok(Self(value))
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl Display for Volume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A boolean setting that is enabled unless a guild turns it off
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultOn(pub bool);
+
+impl Default for DefaultOn {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+impl FromStr for DefaultOn {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<bool>().map(Self).map_err(|_| ())
+    }
+}
+
+impl Display for DefaultOn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}